@@ -0,0 +1,78 @@
+use std::fmt;
+
+// Integer semantics shared by the interpreter and the compiler backends.
+// Int is a 64-bit signed integer and every operation wraps around on overflow.
+// Division truncates towards zero and the remainder has the sign of the
+// dividend, matching C and the x86-64 `idiv` instruction. Dividing by zero is
+// an error instead of a value.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DivisionByZero;
+
+impl fmt::Display for DivisionByZero {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "division by zero")
+    }
+}
+
+impl std::error::Error for DivisionByZero {}
+
+pub fn divide(left: i64, right: i64) -> Result<i64, DivisionByZero> {
+    if right == 0 {
+        return Err(DivisionByZero);
+    }
+    Ok(left.wrapping_div(right))
+}
+
+pub fn remainder(left: i64, right: i64) -> Result<i64, DivisionByZero> {
+    if right == 0 {
+        return Err(DivisionByZero);
+    }
+    Ok(left.wrapping_rem(right))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arithmetic::*;
+
+    #[test]
+    fn division_truncates_towards_zero() {
+        assert_eq!(divide(7, 2), Ok(3));
+        assert_eq!(divide(-7, 2), Ok(-3));
+        assert_eq!(divide(7, -2), Ok(-3));
+        assert_eq!(divide(-7, -2), Ok(3));
+        assert_eq!(divide(0, 5), Ok(0));
+    }
+
+    #[test]
+    fn remainder_has_sign_of_dividend() {
+        assert_eq!(remainder(7, 3), Ok(1));
+        assert_eq!(remainder(-7, 3), Ok(-1));
+        assert_eq!(remainder(7, -3), Ok(1));
+        assert_eq!(remainder(-7, -3), Ok(-1));
+        assert_eq!(remainder(6, 3), Ok(0));
+    }
+
+    #[test]
+    fn quotient_and_remainder_add_up() {
+        for left in -10..=10 {
+            for right in [-4, -3, -1, 1, 2, 5] {
+                let quotient = divide(left, right).unwrap();
+                assert_eq!(quotient * right + remainder(left, right).unwrap(), left);
+            }
+        }
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(divide(1, 0), Err(DivisionByZero));
+        assert_eq!(remainder(1, 0), Err(DivisionByZero));
+        assert_eq!(divide(0, 0).unwrap_err().to_string(), "division by zero");
+    }
+
+    #[test]
+    fn overflow_wraps() {
+        assert_eq!(divide(i64::MIN, -1), Ok(i64::MIN));
+        assert_eq!(remainder(i64::MIN, -1), Ok(0));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::ir::{InstructionKind, IrFunction};
+
+use super::intrinsics::emit_intrinsic;
+use super::locals::Locals;
+
+// System V calling convention, the remaining arguments go on the stack
+const ARGUMENT_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+// Labels are local to the whole file, so they are prefixed with the function
+fn label_name(function: &IrFunction, label: &str) -> String {
+    format!(".L{}_{}", function.name, label)
+}
+
+// Collects lines of assembly, indenting everything except labels and
+// directives that start a function
+struct Emitter {
+    lines: Vec<String>,
+}
+
+impl Emitter {
+    fn emit(&mut self, line: &str) {
+        if line.ends_with(':') {
+            self.lines.push(line.to_string());
+        } else {
+            self.lines.push(format!("    {}", line));
+        }
+    }
+
+    fn emit_all(&mut self, lines: &[String]) {
+        for line in lines {
+            self.emit(line);
+        }
+    }
+}
+
+fn generate_function(function: &IrFunction, out: &mut Emitter) {
+    let locals = Locals::new(function);
+    out.lines.push(String::new());
+    out.emit(&format!(".global {}", function.name));
+    out.emit(&format!(".type {}, @function", function.name));
+    out.emit(&format!("{}:", function.name));
+    out.emit("pushq %rbp");
+    out.emit("movq %rsp, %rbp");
+    out.emit(&format!("subq ${}, %rsp", locals.stack_used()));
+
+    for (index, parameter) in function.parameters.iter().enumerate() {
+        match ARGUMENT_REGISTERS.get(index) {
+            Some(register) => {
+                out.emit(&format!("movq {}, {}", register, locals.get_ref(parameter)))
+            }
+            None => {
+                // Above the saved %rbp and the return address
+                let offset = 16 + 8 * (index - ARGUMENT_REGISTERS.len());
+                out.emit(&format!("movq {}(%rbp), %rax", offset));
+                out.emit(&format!("movq %rax, {}", locals.get_ref(parameter)));
+            }
+        }
+    }
+
+    for (index, instruction) in function.instructions.iter().enumerate() {
+        out.lines.push(String::new());
+        out.emit(&format!("# {}", instruction));
+        match &instruction.kind {
+            InstructionKind::LoadIntConst { value, dest } => {
+                if i32::try_from(*value).is_ok() {
+                    out.emit(&format!("movq ${}, {}", value, locals.get_ref(dest)));
+                } else {
+                    out.emit(&format!("movabsq ${}, %rax", value));
+                    out.emit(&format!("movq %rax, {}", locals.get_ref(dest)));
+                }
+            }
+            InstructionKind::LoadBoolConst { value, dest } => {
+                let value = if *value { 1 } else { 0 };
+                out.emit(&format!("movq ${}, {}", value, locals.get_ref(dest)));
+            }
+            InstructionKind::Copy { source, dest } => {
+                out.emit(&format!("movq {}, %rax", locals.get_ref(source)));
+                out.emit(&format!("movq %rax, {}", locals.get_ref(dest)));
+            }
+            InstructionKind::LoadFunction {
+                function: callee,
+                dest,
+            } => {
+                out.emit(&format!("leaq {}(%rip), %rax", callee.name));
+                out.emit(&format!("movq %rax, {}", locals.get_ref(dest)));
+            }
+            InstructionKind::Call {
+                function: callee,
+                arguments,
+                dest,
+            } => {
+                let argument_refs: Vec<&str> = arguments
+                    .iter()
+                    .map(|argument| locals.get_ref(argument))
+                    .collect();
+                let mut lines = Vec::new();
+                let label = label_name(function, &format!("intrinsic{}", index));
+                // A variable holds the address of the function to call
+                if let Some(slot) = locals.find_ref(callee) {
+                    emit_call(&format!("*{}", slot), &argument_refs, &mut lines);
+                } else if !emit_intrinsic(&callee.name, &argument_refs, &label, &mut lines) {
+                    emit_call(&callee.name, &argument_refs, &mut lines);
+                }
+                out.emit_all(&lines);
+                out.emit(&format!("movq %rax, {}", locals.get_ref(dest)));
+            }
+            InstructionKind::Jump { label } => {
+                out.emit(&format!("jmp {}", label_name(function, label)));
+            }
+            InstructionKind::CondJump {
+                condition,
+                then_label,
+                else_label,
+            } => {
+                out.emit(&format!("cmpq $0, {}", locals.get_ref(condition)));
+                out.emit(&format!("jne {}", label_name(function, then_label)));
+                out.emit(&format!("jmp {}", label_name(function, else_label)));
+            }
+            InstructionKind::Label { name } => {
+                out.emit(&format!("{}:", label_name(function, name)));
+            }
+            InstructionKind::Return { value } => {
+                match value {
+                    Some(value) => out.emit(&format!("movq {}, %rax", locals.get_ref(value))),
+                    None => out.emit("movq $0, %rax"),
+                }
+                out.emit("movq %rbp, %rsp");
+                out.emit("popq %rbp");
+                out.emit("ret");
+            }
+        }
+    }
+}
+
+// Keeps %rsp 16-byte aligned at the call by padding when an odd number of
+// arguments is pushed. `target` is a symbol, or `*` and the operand holding
+// the address for an indirect call.
+fn emit_call(target: &str, arguments: &[&str], lines: &mut Vec<String>) {
+    let stack_arguments = arguments.len().saturating_sub(ARGUMENT_REGISTERS.len());
+    let padding = stack_arguments % 2;
+    if padding == 1 {
+        lines.push("subq $8, %rsp".to_string());
+    }
+    for argument in arguments.iter().skip(ARGUMENT_REGISTERS.len()).rev() {
+        lines.push(format!("pushq {}", argument));
+    }
+    for (argument, register) in arguments.iter().zip(ARGUMENT_REGISTERS) {
+        lines.push(format!("movq {}, {}", argument, register));
+    }
+    lines.push(format!("callq {}", target));
+    if stack_arguments + padding > 0 {
+        lines.push(format!("addq ${}, %rsp", 8 * (stack_arguments + padding)));
+    }
+}
+
+// Produces a complete assembly file in AT&T syntax for GNU as
+pub fn generate_asm(functions: &[IrFunction]) -> String {
+    let mut out = Emitter { lines: Vec::new() };
+    out.emit(".section .text");
+    for function in functions {
+        generate_function(function, &mut out);
+    }
+    let mut asm = out.lines.join("\n");
+    asm.push('\n');
+    asm
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use crate::asm_generator::generate::*;
+    use crate::ir::IrGenerator;
+    use crate::parser::parse;
+    use crate::tokenizer::tokenize;
+    use crate::typechecker::{Type, TypeChecker};
+
+    fn asm_for(source: &str) -> String {
+        let mut expression = parse(tokenize(source.to_string()).unwrap()).unwrap();
+        let mut type_checker = TypeChecker::new();
+        let parameters = vec![Type::Int; 8];
+        type_checker.declare_global("f", Type::function(parameters, Type::Int));
+        type_checker.check(&mut expression).unwrap();
+        let mut generator = IrGenerator::new();
+        generator.declare_function("f");
+        generate_asm(&[generator.generate("main", &expression)])
+    }
+
+    #[test]
+    fn function_frame() {
+        assert_eq!(
+            asm_for("var x = 1"),
+            "    .section .text
+
+    .global main
+    .type main, @function
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+
+    # LoadIntConst(1, x1)
+    movq $1, -8(%rbp)
+
+    # Copy(x1, x)
+    movq -8(%rbp), %rax
+    movq %rax, -16(%rbp)
+
+    # Return()
+    movq $0, %rax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+"
+        );
+    }
+
+    #[test]
+    fn large_constants() {
+        let asm = asm_for("4294967296");
+        assert!(asm.contains("movabsq $4294967296, %rax\n    movq %rax, -8(%rbp)"));
+    }
+
+    #[test]
+    fn arithmetic_and_comparisons() {
+        let asm = asm_for("1 + 2 < 3");
+        assert!(asm.contains("movq -8(%rbp), %rax\n    addq -16(%rbp), %rax"));
+        assert!(asm.contains("cmpq -32(%rbp), %rdx\n    setl %al"));
+    }
+
+    #[test]
+    fn division_and_remainder() {
+        let asm = asm_for("7 / 2 + 7 % 2");
+        assert!(asm.contains("cqto\n    idivq %rcx\n.Lmain_intrinsic2_end:"));
+        assert!(asm.contains("idivq %rcx\n    movq %rdx, %rax\n.Lmain_intrinsic5_end:"));
+    }
+
+    #[test]
+    fn jumps() {
+        let asm = asm_for("if true then 1 else 2");
+        assert!(asm.contains("cmpq $0, -8(%rbp)\n    jne .Lmain_then1\n    jmp .Lmain_else2"));
+        assert!(asm.contains("jmp .Lmain_if_end3"));
+        assert!(asm.contains("\n.Lmain_if_end3:\n"));
+    }
+
+    #[test]
+    fn calls_pass_arguments_in_registers_and_on_the_stack() {
+        let asm = asm_for("f(1, 2, 3, 4, 5, 6, 7, 8)");
+        assert!(asm.contains(
+            "    pushq -64(%rbp)
+    pushq -56(%rbp)
+    movq -8(%rbp), %rdi
+    movq -16(%rbp), %rsi
+    movq -24(%rbp), %rdx
+    movq -32(%rbp), %rcx
+    movq -40(%rbp), %r8
+    movq -48(%rbp), %r9
+    callq f
+    addq $16, %rsp
+    movq %rax, -72(%rbp)"
+        ));
+    }
+
+    #[test]
+    fn output_is_accepted_by_the_assembler() {
+        let asm = asm_for(
+            "var i = 0; while i < 10 do { i = i + 1; if i % 2 == 0 and not (i > 5) then f(i, 1, 2, 3, 4, 5, 6, -7) }",
+        );
+        let directory = std::env::temp_dir();
+        let source = directory.join(format!("scl-asm-test-{}.s", std::process::id()));
+        let object = source.with_extension("o");
+        fs::write(&source, asm).unwrap();
+        let status = Command::new("as")
+            .arg(&source)
+            .arg("-o")
+            .arg(&object)
+            .status();
+        fs::remove_file(&source).unwrap();
+        let _ = fs::remove_file(&object);
+        // Without an assembler installed there is nothing to check against
+        if let Ok(status) = status {
+            assert!(status.success());
+        }
+    }
+}
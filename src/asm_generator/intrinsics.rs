@@ -0,0 +1,72 @@
+// Operators are compiled inline instead of being called. Each intrinsic reads
+// its operands from the given stack slots and leaves its result in %rax.
+
+fn comparison(condition: &str, arguments: &[&str], lines: &mut Vec<String>) {
+    lines.push("xorq %rax, %rax".to_string());
+    lines.push(format!("movq {}, %rdx", arguments[0]));
+    lines.push(format!("cmpq {}, %rdx", arguments[1]));
+    lines.push(format!("set{} %al", condition));
+}
+
+// idivq traps on i64::MIN / -1, so division by -1 is done separately to keep
+// the wrapping semantics of the interpreter. Division by zero exits through
+// the runtime with the same error as the interpreter instead of trapping.
+fn division(result: &str, arguments: &[&str], label: &str, lines: &mut Vec<String>) {
+    lines.push(format!("movq {}, %rax", arguments[0]));
+    lines.push(format!("movq {}, %rcx", arguments[1]));
+    lines.push("testq %rcx, %rcx".to_string());
+    lines.push("je _division_by_zero".to_string());
+    lines.push("cmpq $-1, %rcx".to_string());
+    lines.push(format!("jne {}_idiv", label));
+    match result {
+        "%rax" => lines.push("negq %rax".to_string()),
+        _ => lines.push("xorq %rax, %rax".to_string()),
+    }
+    lines.push(format!("jmp {}_end", label));
+    lines.push(format!("{}_idiv:", label));
+    lines.push("cqto".to_string());
+    lines.push("idivq %rcx".to_string());
+    if result != "%rax" {
+        lines.push(format!("movq {}, %rax", result));
+    }
+    lines.push(format!("{}_end:", label));
+}
+
+// Returns false if the name is not an intrinsic. `label` is a prefix for
+// any local labels the intrinsic needs and must be unique in the file.
+pub fn emit_intrinsic(
+    name: &str,
+    arguments: &[&str],
+    label: &str,
+    lines: &mut Vec<String>,
+) -> bool {
+    match (name, arguments.len()) {
+        ("+" | "-" | "*", 2) => {
+            let operation = match name {
+                "+" => "addq",
+                "-" => "subq",
+                _ => "imulq",
+            };
+            lines.push(format!("movq {}, %rax", arguments[0]));
+            lines.push(format!("{} {}, %rax", operation, arguments[1]));
+        }
+        ("/", 2) => division("%rax", arguments, label, lines),
+        ("%", 2) => division("%rdx", arguments, label, lines),
+        ("==", 2) => comparison("e", arguments, lines),
+        ("!=", 2) => comparison("ne", arguments, lines),
+        ("<", 2) => comparison("l", arguments, lines),
+        ("<=", 2) => comparison("le", arguments, lines),
+        (">", 2) => comparison("g", arguments, lines),
+        (">=", 2) => comparison("ge", arguments, lines),
+        ("unary_-", 1) => {
+            lines.push(format!("movq {}, %rax", arguments[0]));
+            lines.push("negq %rax".to_string());
+        }
+        ("unary_not", 1) => {
+            lines.push(format!("movq {}, %rax", arguments[0]));
+            lines.push("xorq $1, %rax".to_string());
+        }
+        _ => return false,
+    }
+    true
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::ir::{InstructionKind, IrFunction, IrVar};
+
+// Assigns every IR variable of a function its own 8-byte stack slot below
+// the frame pointer
+#[derive(Debug)]
+pub struct Locals {
+    slots: HashMap<IrVar, String>,
+    stack_used: usize,
+}
+
+impl Locals {
+    // Functions called by name are global symbols and do not get a slot,
+    // unlike variables holding the address of a function
+    pub fn new(function: &IrFunction) -> Locals {
+        let mut locals = Locals {
+            slots: HashMap::new(),
+            stack_used: 0,
+        };
+        for parameter in &function.parameters {
+            locals.add(parameter);
+        }
+        for instruction in &function.instructions {
+            match &instruction.kind {
+                InstructionKind::LoadIntConst { dest, .. }
+                | InstructionKind::LoadBoolConst { dest, .. } => locals.add(dest),
+                InstructionKind::Copy { source, dest } => {
+                    locals.add(source);
+                    locals.add(dest);
+                }
+                InstructionKind::LoadFunction { dest, .. } => locals.add(dest),
+                InstructionKind::Call {
+                    arguments, dest, ..
+                } => {
+                    for argument in arguments {
+                        locals.add(argument);
+                    }
+                    locals.add(dest);
+                }
+                InstructionKind::CondJump { condition, .. } => locals.add(condition),
+                InstructionKind::Return { value: Some(value) } => locals.add(value),
+                InstructionKind::Return { value: None } => {}
+                InstructionKind::Jump { .. } | InstructionKind::Label { .. } => {}
+            }
+        }
+        locals
+    }
+
+    fn add(&mut self, var: &IrVar) {
+        if !self.slots.contains_key(var) {
+            self.stack_used += 8;
+            self.slots
+                .insert(var.clone(), format!("-{}(%rbp)", self.stack_used));
+        }
+    }
+
+    // The stack slot of the variable, if it has one
+    pub fn find_ref(&self, var: &IrVar) -> Option<&str> {
+        self.slots.get(var).map(String::as_str)
+    }
+
+    // Operand referring to the stack slot of the variable
+    pub fn get_ref(&self, var: &IrVar) -> &str {
+        match self.find_ref(var) {
+            Some(slot) => slot,
+            None => panic!("IR variable '{}' has no stack slot", var),
+        }
+    }
+
+    // Kept a multiple of 16 so that calls see an aligned stack
+    pub fn stack_used(&self) -> usize {
+        self.stack_used.div_ceil(16) * 16
+    }
+}
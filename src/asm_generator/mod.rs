@@ -0,0 +1,6 @@
+mod generate;
+mod intrinsics;
+mod locals;
+
+pub use generate::generate_asm;
+pub use locals::Locals;
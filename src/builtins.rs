@@ -0,0 +1,11 @@
+use crate::typechecker::Type;
+
+// Functions every program can call. The compiled versions are in
+// driver/runtime.s and the interpreted ones in interpreter/builtins.rs.
+pub fn builtin_types() -> Vec<(&'static str, Type)> {
+    vec![
+        ("print_int", Type::function(vec![Type::Int], Type::Unit)),
+        ("print_bool", Type::function(vec![Type::Bool], Type::Unit)),
+        ("read_int", Type::function(vec![], Type::Int)),
+    ]
+}
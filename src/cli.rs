@@ -0,0 +1,606 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use crate::diagnostics::Diagnostic;
+use crate::driver::{
+    check_source, check_source_all, compile_source, format_source, generate_program_asm,
+    generate_program_ir, parse_source, CompileError,
+};
+use crate::interpreter::{stdin_lines, Interpreter, RuntimeError};
+use crate::lsp;
+use crate::repl;
+use crate::server::serve;
+use crate::tokenizer::{tokenize_named, Token};
+use crate::typechecker::{function_type, Type};
+
+pub const USAGE: &str = "usage: scl <command> [file] [-o output] [--check]
+
+commands:
+    tokenize   print the tokens
+    parse      print the syntax tree
+    typecheck  print the types of the functions and the program
+    check      report every error in the program, printing nothing if none
+    ir         print the intermediate representation
+    asm        print the generated assembly
+    run        run the program with the interpreter
+    compile    compile to an executable, requires -o
+    fmt        print the program in the canonical style, or with --check
+               only fail if it is not in it
+    repl       read, evaluate and print programs interactively
+    lsp        run a language server on stdin and stdout
+    serve      answer compile requests over TCP, see below
+
+Without a file, or with '-', the program is read from stdin. --help or -h
+prints this message.
+
+usage: scl serve [--host host] [--port port]
+
+Listens on 127.0.0.1:3000 by default. Each connection sends one JSON request,
+{\"command\": \"compile\", \"code\": ...} or {\"command\": \"ping\"}, and gets
+one JSON response back.";
+
+// Name used in locations when the program comes from stdin
+const STDIN_NAME: &str = "<stdin>";
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 3000;
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Io(io::Error),
+    Compile(CompileError),
+    // Everything found by `check`, never empty
+    Errors(Vec<CompileError>),
+    Runtime(RuntimeError),
+    // `fmt --check` found a file that formatting would change
+    Unformatted(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            CliError::Compile(error) => vec![error.diagnostic()],
+            CliError::Errors(errors) => errors.iter().map(CompileError::diagnostic).collect(),
+            CliError::Runtime(error) => vec![error.diagnostic()],
+            CliError::Usage(_) | CliError::Io(_) | CliError::Unformatted(_) => {
+                vec![Diagnostic::new(self.to_string())]
+            }
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Io(error) => write!(f, "{}", error),
+            CliError::Compile(error) => write!(f, "{}", error),
+            CliError::Errors(errors) => {
+                let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            CliError::Runtime(error) => write!(f, "{}", error),
+            CliError::Unformatted(filename) => write!(f, "{} is not formatted", filename),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> CliError {
+        CliError::Io(error)
+    }
+}
+
+impl From<CompileError> for CliError {
+    fn from(error: CompileError) -> CliError {
+        CliError::Compile(error)
+    }
+}
+
+impl From<RuntimeError> for CliError {
+    fn from(error: RuntimeError) -> CliError {
+        CliError::Runtime(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Tokenize,
+    Parse,
+    Typecheck,
+    Check,
+    Ir,
+    Asm,
+    Run,
+    Compile,
+    Format,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "tokenize" => Some(Command::Tokenize),
+            "parse" => Some(Command::Parse),
+            "typecheck" => Some(Command::Typecheck),
+            "check" => Some(Command::Check),
+            "ir" => Some(Command::Ir),
+            "asm" => Some(Command::Asm),
+            "run" => Some(Command::Run),
+            "compile" => Some(Command::Compile),
+            "fmt" => Some(Command::Format),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    command: Command,
+    // None reads stdin
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    // `fmt --check`
+    check: bool,
+}
+
+fn usage_error(message: &str) -> CliError {
+    CliError::Usage(message.to_string())
+}
+
+fn parse_arguments(arguments: &[String]) -> Result<Invocation, CliError> {
+    let (name, rest) = match arguments.split_first() {
+        Some(split) => split,
+        None => return Err(usage_error("no command given")),
+    };
+    let command = match Command::from_name(name) {
+        Some(command) => command,
+        None => return Err(CliError::Usage(format!("unknown command '{}'", name))),
+    };
+    let mut input = None;
+    let mut output = None;
+    let mut check = false;
+    let mut arguments = rest.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-o" => match arguments.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => return Err(usage_error("-o requires an output file")),
+            },
+            "--check" => check = true,
+            "-" if input.is_none() => input = Some(None),
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(CliError::Usage(format!("unknown option '{}'", flag)))
+            }
+            path if input.is_none() => input = Some(Some(PathBuf::from(path))),
+            _ => return Err(usage_error("only one input file can be given")),
+        }
+    }
+    match (command, &output) {
+        (Command::Compile, None) => return Err(usage_error("compile requires -o <output>")),
+        (Command::Compile, Some(_)) | (_, None) => {}
+        (_, Some(_)) => return Err(usage_error("-o can only be used with compile")),
+    }
+    if check && command != Command::Format {
+        return Err(usage_error("--check can only be used with fmt"));
+    }
+    Ok(Invocation {
+        command,
+        input: input.flatten(),
+        output,
+        check,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct ServeOptions {
+    host: String,
+    port: u16,
+}
+
+fn parse_serve_arguments(arguments: &[String]) -> Result<ServeOptions, CliError> {
+    let mut options = ServeOptions {
+        host: DEFAULT_HOST.to_string(),
+        port: DEFAULT_PORT,
+    };
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--host" => match arguments.next() {
+                Some(host) => options.host = host.clone(),
+                None => return Err(usage_error("--host requires a host")),
+            },
+            "--port" => match arguments.next().map(|port| port.parse()) {
+                Some(Ok(port)) => options.port = port,
+                Some(Err(_)) => return Err(usage_error("--port requires a number up to 65535")),
+                None => return Err(usage_error("--port requires a port")),
+            },
+            other => return Err(CliError::Usage(format!("unexpected argument '{}'", other))),
+        }
+    }
+    Ok(options)
+}
+
+// How `tokenize` shows a token: its position, type and text
+pub(crate) fn token_line(token: &Token) -> String {
+    let location = token.location();
+    format!(
+        "{}:{}\t{:?}\t{}",
+        location.line(),
+        location.col(),
+        token.token_type(),
+        token.value()
+    )
+}
+
+// Returns the source and the name to use for it in locations
+fn read_input(input: &Option<PathBuf>) -> io::Result<(String, String)> {
+    match input {
+        Some(path) => Ok((fs::read_to_string(path)?, path.display().to_string())),
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            Ok((source, STDIN_NAME.to_string()))
+        }
+    }
+}
+
+fn execute(
+    invocation: &Invocation,
+    source: String,
+    filename: &str,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    match invocation.command {
+        Command::Tokenize => {
+            let tokens = tokenize_named(source, filename).map_err(CompileError::from)?;
+            for token in tokens {
+                writeln!(out, "{}", token_line(&token))?;
+            }
+        }
+        Command::Parse => writeln!(out, "{}", parse_source(source, filename)?)?,
+        Command::Typecheck => {
+            let module = check_source(source, filename)?;
+            for function in &module.functions {
+                let ty = function_type(function).map_err(CompileError::from)?;
+                writeln!(out, "{}: {}", function.name, ty)?;
+            }
+            match &module.body.ty {
+                Some(ty) => writeln!(out, "{}", ty)?,
+                None => writeln!(out, "{}", Type::Unit)?,
+            }
+        }
+        Command::Check => {
+            check_source_all(source, filename).map_err(CliError::Errors)?;
+        }
+        Command::Ir => {
+            for function in generate_program_ir(source, filename)? {
+                write!(out, "{}", function)?;
+            }
+        }
+        Command::Asm => write!(out, "{}", generate_program_asm(source, filename)?)?,
+        Command::Run => {
+            let module = check_source(source, filename)?;
+            Interpreter::new().evaluate_module(&module)?;
+        }
+        Command::Compile => {
+            // `parse_arguments` makes sure an output is given
+            let output = invocation.output.as_deref().expect("compile requires -o");
+            compile_source(source, filename, output)?;
+        }
+        Command::Format => {
+            let formatted = format_source(source.clone(), filename)?;
+            if !invocation.check {
+                write!(out, "{}", formatted)?;
+            } else if formatted != source {
+                return Err(CliError::Unformatted(filename.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Runs the command line given without the program name, writing the
+// output of the inspection commands to `out`
+pub fn run(arguments: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    run_keeping_source(arguments, out, &mut None, false)
+}
+
+// Like `run`, but writes any error to `errors` together with the source
+// lines it points at. Returns the exit code.
+pub fn run_and_report(
+    arguments: &[String],
+    out: &mut dyn Write,
+    errors: &mut dyn Write,
+    colour: bool,
+) -> u8 {
+    let mut source = None;
+    match run_keeping_source(arguments, out, &mut source, colour) {
+        Ok(()) => 0,
+        Err(error) => {
+            let source = source.unwrap_or_default();
+            let rendered: Vec<String> = error
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.render(&source, colour))
+                .collect();
+            let _ = write!(errors, "{}", rendered.join("\n"));
+            error.exit_code()
+        }
+    }
+}
+
+// Stores the program in `source` once it has been read, so that errors can
+// be shown with it. `colour` applies to the errors shown by the REPL.
+fn run_keeping_source(
+    arguments: &[String],
+    out: &mut dyn Write,
+    source: &mut Option<String>,
+    colour: bool,
+) -> Result<(), CliError> {
+    if arguments
+        .iter()
+        .any(|argument| argument == "--help" || argument == "-h")
+    {
+        return Ok(writeln!(out, "{}", USAGE)?);
+    }
+    // The servers and the REPL take no input file, so they have their own
+    // options
+    match arguments.split_first() {
+        Some((name, rest)) if name == "serve" => {
+            let options = parse_serve_arguments(rest)?;
+            return Ok(serve(&options.host, options.port)?);
+        }
+        Some((name, rest)) if name == "repl" => {
+            if let Some(argument) = rest.first() {
+                return Err(CliError::Usage(format!(
+                    "unexpected argument '{}'",
+                    argument
+                )));
+            }
+            return Ok(repl::run(stdin_lines(), out, colour)?);
+        }
+        Some((name, rest)) if name == "lsp" => {
+            if let Some(argument) = rest.first() {
+                return Err(CliError::Usage(format!(
+                    "unexpected argument '{}'",
+                    argument
+                )));
+            }
+            return Ok(lsp::run(&mut io::stdin().lock(), out)?);
+        }
+        _ => {}
+    }
+    let invocation = parse_arguments(arguments)?;
+    let (program, filename) = read_input(&invocation.input)?;
+    *source = Some(program.clone());
+    execute(&invocation, program, &filename, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::cli::*;
+
+    fn arguments(line: &str) -> Vec<String> {
+        line.split_whitespace()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    fn run_on_source(command: &str, source: &str) -> Result<String, CliError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "scl-cli-test-{}-{}.scl",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, source).unwrap();
+        let mut out = Vec::new();
+        let mut line = arguments(command);
+        line.push(path.display().to_string());
+        let result = run(&line, &mut out);
+        fs::remove_file(&path).unwrap();
+        result.map(|_| String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn argument_parsing() {
+        assert_eq!(
+            parse_arguments(&arguments("compile a.scl -o a")).unwrap(),
+            Invocation {
+                command: Command::Compile,
+                input: Some(PathBuf::from("a.scl")),
+                output: Some(PathBuf::from("a")),
+                check: false,
+            }
+        );
+        assert_eq!(
+            parse_arguments(&arguments("ir -")).unwrap(),
+            Invocation {
+                command: Command::Ir,
+                input: None,
+                output: None,
+                check: false,
+            }
+        );
+        assert_eq!(
+            parse_arguments(&arguments("fmt --check a.scl")).unwrap(),
+            Invocation {
+                command: Command::Format,
+                input: Some(PathBuf::from("a.scl")),
+                output: None,
+                check: true,
+            }
+        );
+        let error = |line: &str| match parse_arguments(&arguments(line)) {
+            Err(CliError::Usage(message)) => message,
+            other => panic!("expected a usage error, got {:?}", other),
+        };
+        assert_eq!(error(""), "no command given");
+        assert_eq!(error("build a.scl"), "unknown command 'build'");
+        assert_eq!(error("compile a.scl"), "compile requires -o <output>");
+        assert_eq!(error("compile a.scl -o"), "-o requires an output file");
+        assert_eq!(error("run a.scl -o a"), "-o can only be used with compile");
+        assert_eq!(error("run a.scl b.scl"), "only one input file can be given");
+        assert_eq!(error("run --fast a.scl"), "unknown option '--fast'");
+        assert_eq!(
+            error("run --check a.scl"),
+            "--check can only be used with fmt"
+        );
+    }
+
+    #[test]
+    fn serve_argument_parsing() {
+        assert_eq!(
+            parse_serve_arguments(&[]).unwrap(),
+            ServeOptions {
+                host: "127.0.0.1".to_string(),
+                port: 3000,
+            }
+        );
+        assert_eq!(
+            parse_serve_arguments(&arguments("--port 8080 --host 0.0.0.0")).unwrap(),
+            ServeOptions {
+                host: "0.0.0.0".to_string(),
+                port: 8080,
+            }
+        );
+        let error = |line: &str| match parse_serve_arguments(&arguments(line)) {
+            Err(CliError::Usage(message)) => message,
+            other => panic!("expected a usage error, got {:?}", other),
+        };
+        assert_eq!(error("--port"), "--port requires a port");
+        assert_eq!(
+            error("--port 70000"),
+            "--port requires a number up to 65535"
+        );
+        assert_eq!(error("--host"), "--host requires a host");
+        assert_eq!(error("a.scl"), "unexpected argument 'a.scl'");
+    }
+
+    #[test]
+    fn inspection_commands() {
+        assert_eq!(
+            run_on_source("tokenize", "var x = 1;").unwrap(),
+            "1:1\tKeyword(Var)\tvar
+1:5\tIdentifier\tx
+1:7\tOperator(Assign)\t=
+1:9\tIntLiteral\t1
+1:10\tPunctuation(Semicolon)\t;
+"
+        );
+        assert_eq!(
+            run_on_source("parse", "fun f(a: Int): Int { a } f(1) + 2").unwrap(),
+            "fun f(a: Int): Int {a}\n(f(1) + 2)\n"
+        );
+        assert_eq!(
+            run_on_source("typecheck", "fun f(a: Int, b: Bool) {} 1 < 2").unwrap(),
+            "f: (Int, Bool) => Unit\nBool\n"
+        );
+        assert_eq!(
+            run_on_source("ir", "print_int(1)").unwrap(),
+            "main():
+    LoadIntConst(1, x1)
+    Call(print_int, [x1], x2)
+    Return()
+"
+        );
+        assert!(run_on_source("asm", "1").unwrap().contains("\nmain:\n"));
+        assert_eq!(run_on_source("run", "var x = 1; x + 1").unwrap(), "");
+    }
+
+    #[test]
+    fn errors() {
+        let error = run_on_source("typecheck", "1 + true").unwrap_err();
+        assert!(matches!(error, CliError::Compile(CompileError::Type(_))));
+        assert_eq!(error.exit_code(), 1);
+        let error = run_on_source("run", "1 / 0").unwrap_err();
+        assert!(matches!(error, CliError::Runtime(_)));
+        assert!(error.to_string().starts_with("division by zero at "));
+        let error = run(&arguments("parse missing-file.scl"), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, CliError::Io(_)));
+        let error = run(&arguments("frobnicate"), &mut Vec::new()).unwrap_err();
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[test]
+    fn help() {
+        for line in ["--help", "-h", "compile a.scl --help"] {
+            let mut out = Vec::new();
+            run(&arguments(line), &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", USAGE));
+        }
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(run_on_source("check", "var x = 1; x").unwrap(), "");
+        match run_on_source("check", "var x = $1;\nx +;\n{ 1 2 }") {
+            Err(CliError::Errors(errors)) => assert_eq!(errors.len(), 3),
+            other => panic!("expected errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn format() {
+        let source = "var x = 1; // one\nif x < 2 then { x = 2 } else x";
+        let formatted = "var x = 1; // one\nif x < 2 then {\n    x = 2\n} else x\n";
+        assert_eq!(run_on_source("fmt", source).unwrap(), formatted);
+        assert_eq!(run_on_source("fmt --check", formatted).unwrap(), "");
+        assert_eq!(run_on_source("fmt --check", "").unwrap(), "");
+        let error = run_on_source("fmt --check", source).unwrap_err();
+        assert!(matches!(error, CliError::Unformatted(_)));
+        assert!(error.to_string().ends_with(".scl is not formatted"));
+        assert_eq!(error.exit_code(), 1);
+        let error = run_on_source("fmt", "var x = ;").unwrap_err();
+        assert!(matches!(error, CliError::Compile(CompileError::Parse(_))));
+    }
+
+    #[test]
+    fn error_reports() {
+        let path = std::env::temp_dir().join(format!("scl-cli-report-{}.scl", std::process::id()));
+        fs::write(&path, "var x = 1;\nx + true").unwrap();
+        let mut line = arguments("typecheck");
+        line.push(path.display().to_string());
+        let mut errors = Vec::new();
+        let code = run_and_report(&line, &mut Vec::new(), &mut errors, false);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(code, 1);
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            format!(
+                "error: operator '+' expects operands of type Int, got Int and Bool
+ --> {}:2:1
+  |
+2 | x + true
+  | ^^^^^^^^
+",
+                path.display()
+            )
+        );
+        let mut errors = Vec::new();
+        let code = run_and_report(
+            &arguments("frobnicate"),
+            &mut Vec::new(),
+            &mut errors,
+            false,
+        );
+        assert_eq!(code, 2);
+        assert!(String::from_utf8(errors)
+            .unwrap()
+            .starts_with("error: unknown command 'frobnicate'\n\nusage: "));
+    }
+}
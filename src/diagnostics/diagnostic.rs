@@ -0,0 +1,113 @@
+use crate::tokenizer::Location;
+
+// `length` bytes of source starting at `location`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    location: Location,
+    length: usize,
+}
+
+impl Span {
+    pub fn new(location: Location, length: usize) -> Span {
+        Span { location, length }
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+// A secondary span pointing at something related to the error
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    span: Span,
+    message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: String) -> Label {
+        Label { span, message }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    message: String,
+    // None for errors that are not about the source, like missing files
+    span: Option<Span>,
+    // Shown next to the underline of the primary span
+    primary_label: Option<String>,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String) -> Diagnostic {
+        Diagnostic {
+            message,
+            span: None,
+            primary_label: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn at(message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            span: Some(span),
+            ..Diagnostic::new(message)
+        }
+    }
+
+    pub fn with_primary_label(mut self, message: &str) -> Diagnostic {
+        self.primary_label = Some(message.to_string());
+        self
+    }
+
+    pub fn with_label(mut self, label: Label) -> Diagnostic {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_labels(mut self, labels: &[Label]) -> Diagnostic {
+        self.labels.extend_from_slice(labels);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    pub fn primary_label(&self) -> Option<&str> {
+        self.primary_label.as_deref()
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+}
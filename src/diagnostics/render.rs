@@ -0,0 +1,235 @@
+use super::diagnostic::{Diagnostic, Span};
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+struct Painter {
+    colour: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// A span to underline below its source line
+struct Annotation<'a> {
+    span: &'a Span,
+    message: Option<&'a str>,
+    primary: bool,
+}
+
+// The whitespace before the underline, keeping tabs so that it lines up with
+// the source line, and the width of the underline in characters
+fn underline_position(line: &str, span: &Span) -> (String, usize) {
+    let characters: Vec<char> = line.chars().collect();
+    let start = (span.location().col() - 1).min(characters.len());
+    let indent = characters[..start]
+        .iter()
+        .map(|&character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+    let mut bytes = 0;
+    let mut width = 0;
+    for character in &characters[start..] {
+        if bytes >= span.length() {
+            break;
+        }
+        bytes += character.len_utf8();
+        width += 1;
+    }
+    (indent, width.max(1))
+}
+
+impl Diagnostic {
+    // Renders the diagnostic like rustc does, showing the lines of `source`
+    // the spans point at
+    pub fn render(&self, source: &str, colour: bool) -> String {
+        let painter = Painter { colour };
+        let mut output = format!(
+            "{}{}\n",
+            painter.paint(RED, "error"),
+            painter.paint(BOLD, &format!(": {}", self.message()))
+        );
+        let lines: Vec<&str> = source.lines().collect();
+        let mut annotations = Vec::new();
+        if let Some(span) = self.span() {
+            annotations.push(Annotation {
+                span,
+                message: self.primary_label(),
+                primary: true,
+            });
+        }
+        for label in self.labels() {
+            annotations.push(Annotation {
+                span: label.span(),
+                message: Some(label.message()),
+                primary: false,
+            });
+        }
+        // Spans outside of the source can only be shown by their location
+        annotations.retain(|annotation| {
+            (1..=lines.len()).contains(&annotation.span.location().line())
+                && annotation.span.location().col() > 0
+        });
+        let width = annotations
+            .iter()
+            .map(|annotation| annotation.span.location().line().to_string().len())
+            .max()
+            .unwrap_or(1);
+        let padding = " ".repeat(width);
+        if let Some(span) = self.span() {
+            output += &format!(
+                "{}{} {}\n",
+                padding,
+                painter.paint(BLUE, "-->"),
+                span.location()
+            );
+        }
+        if !annotations.is_empty() {
+            output += &format!("{} {}\n", padding, painter.paint(BLUE, "|"));
+            let mut line_numbers: Vec<usize> = annotations
+                .iter()
+                .map(|annotation| annotation.span.location().line())
+                .collect();
+            line_numbers.sort();
+            line_numbers.dedup();
+            let mut previous = None;
+            for number in line_numbers {
+                if previous.is_some_and(|previous| number > previous + 1) {
+                    output += &format!("{}\n", painter.paint(BLUE, "..."));
+                }
+                previous = Some(number);
+                let line = lines[number - 1];
+                output += &format!(
+                    "{} {}\n",
+                    painter.paint(BLUE, &format!("{:>width$} |", number)),
+                    line
+                );
+                for annotation in &annotations {
+                    if annotation.span.location().line() != number {
+                        continue;
+                    }
+                    let (indent, underline_width) = underline_position(line, annotation.span);
+                    let (style, mark) = if annotation.primary {
+                        (RED, "^")
+                    } else {
+                        (BLUE, "-")
+                    };
+                    let mut underline = mark.repeat(underline_width);
+                    if let Some(message) = annotation.message {
+                        underline = format!("{} {}", underline, message);
+                    }
+                    output += &format!(
+                        "{} {}{}\n",
+                        padding,
+                        painter.paint(BLUE, &format!("| {}", indent)),
+                        painter.paint(style, &underline)
+                    );
+                }
+            }
+        }
+        for note in self.notes() {
+            output += &format!(
+                "{} {} {}\n",
+                padding,
+                painter.paint(BLUE, "="),
+                painter.paint(BOLD, &format!("note: {}", note))
+            );
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::*;
+    use crate::tokenizer::{tokenize_named, Token};
+
+    const SOURCE: &str = "fun f(a: Int) {}\n\n\nvar x = 1;\nvar y\t= 1 +  true;\n";
+
+    fn token(value: &str) -> Token {
+        tokenize_named(SOURCE.to_string(), "a.scl")
+            .unwrap()
+            .into_iter()
+            .find(|token| token.value() == value)
+            .unwrap()
+    }
+
+    fn span(value: &str) -> Span {
+        let token = token(value);
+        Span::new(token.location().clone(), token.span().len())
+    }
+
+    #[test]
+    fn primary_span() {
+        let diagnostic = Diagnostic::at("expected Int, got Bool".to_string(), span("true"))
+            .with_primary_label("this is a Bool");
+        assert_eq!(
+            diagnostic.render(SOURCE, false),
+            "error: expected Int, got Bool
+ --> a.scl:5:14
+  |
+5 | var y\t= 1 +  true;
+  |      \t       ^^^^ this is a Bool
+"
+        );
+    }
+
+    #[test]
+    fn labels_and_notes() {
+        let diagnostic = Diagnostic::at("'x' is already defined".to_string(), span("y"))
+            .with_label(Label::new(span("x"), "first defined here".to_string()))
+            .with_label(Label::new(span("f"), "unrelated".to_string()))
+            .with_note("names can only be declared once in a block");
+        assert_eq!(
+            diagnostic.render(SOURCE, false),
+            "error: 'x' is already defined
+ --> a.scl:5:5
+  |
+1 | fun f(a: Int) {}
+  |     - unrelated
+...
+4 | var x = 1;
+  |     - first defined here
+5 | var y\t= 1 +  true;
+  |     ^
+  = note: names can only be declared once in a block
+"
+        );
+    }
+
+    #[test]
+    fn without_source() {
+        let diagnostic = Diagnostic::new("no such file".to_string()).with_note("check the path");
+        assert_eq!(
+            diagnostic.render("", false),
+            "error: no such file\n  = note: check the path\n"
+        );
+        let diagnostic = Diagnostic::at("unexpected end".to_string(), span("fun"));
+        assert_eq!(
+            diagnostic.render("", false),
+            "error: unexpected end\n --> a.scl:1:1\n"
+        );
+    }
+
+    #[test]
+    fn colours() {
+        let diagnostic = Diagnostic::at("bad".to_string(), span("fun"));
+        assert_eq!(
+            diagnostic.render(SOURCE, true),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: bad\x1b[0m
+ \x1b[1;34m-->\x1b[0m a.scl:1:1
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1 |\x1b[0m fun f(a: Int) {}
+  \x1b[1;34m| \x1b[0m\x1b[1;31m^^^\x1b[0m
+"
+        );
+    }
+}
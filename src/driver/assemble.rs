@@ -0,0 +1,139 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Provides _start and the built-in functions, linked into every program
+const RUNTIME_ASM: &str = include_str!("runtime.s");
+
+// An external tool could not be run or reported an error
+#[derive(Debug)]
+pub struct ToolError {
+    tool: String,
+    message: String,
+}
+
+impl ToolError {
+    fn new(tool: &str, message: String) -> ToolError {
+        ToolError {
+            tool: tool.to_string(),
+            message,
+        }
+    }
+
+    pub fn tool(&self) -> &str {
+        &self.tool
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed: {}", self.tool, self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+// Scratch directory that is removed when dropped, also on errors
+pub(super) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(super) fn new() -> io::Result<TempDir> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "scl-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn run(tool: &str, command: &mut Command) -> Result<(), ToolError> {
+    let output = command
+        .output()
+        .map_err(|error| ToolError::new(tool, error.to_string()))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let message = if stderr.is_empty() {
+        output.status.to_string()
+    } else {
+        stderr
+    };
+    Err(ToolError::new(tool, message))
+}
+
+// Assembles the program together with the runtime using `as` and links a
+// static executable with `ld`
+pub fn assemble_and_link(asm: &str, output: &Path) -> Result<(), ToolError> {
+    let directory =
+        TempDir::new().map_err(|error| ToolError::new("temporary directory", error.to_string()))?;
+    let mut objects = Vec::new();
+    for (name, source) in [("runtime", RUNTIME_ASM), ("program", asm)] {
+        let source_path = directory.path.join(format!("{}.s", name));
+        let object_path = directory.path.join(format!("{}.o", name));
+        fs::write(&source_path, source).map_err(|error| ToolError::new("as", error.to_string()))?;
+        run(
+            "as",
+            Command::new("as")
+                .arg("--64")
+                .arg(&source_path)
+                .arg("-o")
+                .arg(&object_path),
+        )?;
+        objects.push(object_path);
+    }
+    run(
+        "ld",
+        Command::new("ld")
+            .arg("-static")
+            .arg("-o")
+            .arg(output)
+            .args(&objects),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::assemble::*;
+
+    #[test]
+    fn assembler_errors_are_reported() {
+        let output = std::env::temp_dir().join(format!("scl-bad-asm-{}", std::process::id()));
+        let error = assemble_and_link("    not_an_instruction %rax\n", &output).unwrap_err();
+        assert_eq!(error.tool(), "as");
+        assert!(error.message().contains("not_an_instruction"));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn temporary_files_are_removed() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path.clone();
+        fs::write(path.join("file.s"), "").unwrap();
+        drop(directory);
+        assert!(!path.exists());
+    }
+}
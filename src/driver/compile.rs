@@ -0,0 +1,416 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::asm_generator::generate_asm;
+use crate::diagnostics::Diagnostic;
+use crate::formatter::format_module;
+use crate::ir::{generate_module_ir, IrFunction};
+use crate::parser::{
+    parse_input, parse_module, parse_module_recovering, Expression, ExpressionKind, Module,
+    ParseError,
+};
+use crate::tokenizer::{
+    tokenize_named, tokenize_recovering, tokenize_with_comments, LexError, Location, Token,
+    TokenType,
+};
+use crate::typechecker::{typecheck_module, TypeError};
+
+use super::assemble::{assemble_and_link, TempDir, ToolError};
+
+#[derive(Debug)]
+pub enum CompileError {
+    Io(io::Error),
+    Lex(LexError),
+    Parse(ParseError),
+    Type(TypeError),
+    Tool(ToolError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Io(error) => write!(f, "{}", error),
+            CompileError::Lex(error) => write!(f, "{}", error),
+            CompileError::Parse(error) => write!(f, "{}", error),
+            CompileError::Type(error) => write!(f, "{}", error),
+            CompileError::Tool(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl CompileError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            CompileError::Lex(error) => error.diagnostic(),
+            CompileError::Parse(error) => error.diagnostic(),
+            CompileError::Type(error) => error.diagnostic(),
+            CompileError::Io(_) | CompileError::Tool(_) => Diagnostic::new(self.to_string()),
+        }
+    }
+
+    // Where in the source the error is, for errors found in the source
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            CompileError::Lex(error) => Some(error.location()),
+            CompileError::Parse(error) => Some(error.location()),
+            CompileError::Type(error) => Some(error.location()),
+            CompileError::Io(_) | CompileError::Tool(_) => None,
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<io::Error> for CompileError {
+    fn from(error: io::Error) -> CompileError {
+        CompileError::Io(error)
+    }
+}
+
+impl From<LexError> for CompileError {
+    fn from(error: LexError) -> CompileError {
+        CompileError::Lex(error)
+    }
+}
+
+impl From<ParseError> for CompileError {
+    fn from(error: ParseError) -> CompileError {
+        CompileError::Parse(error)
+    }
+}
+
+impl From<TypeError> for CompileError {
+    fn from(error: TypeError) -> CompileError {
+        CompileError::Type(error)
+    }
+}
+
+impl From<ToolError> for CompileError {
+    fn from(error: ToolError) -> CompileError {
+        CompileError::Tool(error)
+    }
+}
+
+pub fn parse_source(source: String, filename: &str) -> Result<Module, CompileError> {
+    Ok(parse_module(tokenize_named(source, filename)?, filename)?)
+}
+
+// Parses and type checks, returning the module with types filled in
+pub fn check_source(source: String, filename: &str) -> Result<Module, CompileError> {
+    let mut module = parse_source(source, filename)?;
+    typecheck_module(&mut module)?;
+    Ok(module)
+}
+
+// Reports every lexical and syntax error instead of stopping at the first,
+// in the order they appear in the source. Type checking only runs when there
+// are none, and stops at its first error.
+pub fn check_source_all(source: String, filename: &str) -> Result<Module, Vec<CompileError>> {
+    let (tokens, lex_errors) = tokenize_recovering(source, filename);
+    let (mut module, parse_errors) = parse_module_recovering(tokens, filename);
+    let mut errors: Vec<CompileError> = lex_errors.into_iter().map(CompileError::from).collect();
+    errors.extend(parse_errors.into_iter().map(CompileError::from));
+    errors.sort_by_key(|error| error.location().map(Location::offset));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    match typecheck_module(&mut module) {
+        Ok(_) => Ok(module),
+        Err(error) => Err(vec![error.into()]),
+    }
+}
+
+// Prints the program back in the canonical style, comments included
+pub fn format_source(source: String, filename: &str) -> Result<String, CompileError> {
+    let (tokens, lex_errors) = tokenize_with_comments(source.clone(), filename);
+    if let Some(error) = lex_errors.into_iter().next() {
+        return Err(error.into());
+    }
+    let code: Vec<Token> = tokens
+        .iter()
+        .filter(|token| *token.token_type() != TokenType::Comment)
+        .cloned()
+        .collect();
+    // A file with only comments has no code to parse but is still formatted
+    let module = if code.is_empty() {
+        Module {
+            functions: Vec::new(),
+            body: Expression::new(
+                ExpressionKind::Block {
+                    expressions: Vec::new(),
+                    result: None,
+                },
+                Location::start(filename),
+                Location::start(filename),
+            ),
+        }
+    } else {
+        parse_input(code, filename)?
+    };
+    Ok(format_module(&module, &tokens, &source))
+}
+
+pub fn generate_program_ir(
+    source: String,
+    filename: &str,
+) -> Result<Vec<IrFunction>, CompileError> {
+    Ok(generate_module_ir(&check_source(source, filename)?))
+}
+
+// Runs every stage up to assembly generation
+pub fn generate_program_asm(source: String, filename: &str) -> Result<String, CompileError> {
+    Ok(generate_asm(&generate_program_ir(source, filename)?))
+}
+
+pub fn compile_source(source: String, filename: &str, output: &Path) -> Result<(), CompileError> {
+    let asm = generate_program_asm(source, filename)?;
+    assemble_and_link(&asm, output)?;
+    Ok(())
+}
+
+// Compiles into a temporary file and returns the contents of the executable
+pub fn compile_to_bytes(source: String, filename: &str) -> Result<Vec<u8>, CompileError> {
+    let directory = TempDir::new()?;
+    let output = directory.path().join("program");
+    compile_source(source, filename, &output)?;
+    Ok(fs::read(output)?)
+}
+
+pub fn compile_file(path: &Path, output: &Path) -> Result<(), CompileError> {
+    let source = fs::read_to_string(path)?;
+    compile_source(source, &path.display().to_string(), output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::driver::compile::*;
+    use crate::interpreter::{io_builtins, Interpreter};
+
+    fn compile_and_run(source: &str, input: &str) -> Output {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let output = std::env::temp_dir().join(format!(
+            "scl-compile-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        compile_source(source.to_string(), "test.scl", &output).unwrap();
+        let mut child = Command::new(&output)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let result = child.wait_with_output().unwrap();
+        fs::remove_file(&output).unwrap();
+        result
+    }
+
+    fn stdout(output: &Output) -> String {
+        String::from_utf8(output.stdout.clone()).unwrap()
+    }
+
+    // What the interpreter prints for the program, without any input
+    fn interpret(source: &str) -> String {
+        let output = Rc::new(RefCell::new(String::new()));
+        let sink = output.clone();
+        let mut interpreter = Interpreter::new();
+        for function in io_builtins(
+            Rc::new(|| None),
+            Rc::new(move |text: &str| sink.borrow_mut().push_str(text)),
+        ) {
+            interpreter.define_builtin(function);
+        }
+        let module = check_source(source.to_string(), "test.scl").unwrap();
+        interpreter.evaluate_module(&module).unwrap();
+        output.take()
+    }
+
+    #[test]
+    fn unit_values_compare_like_in_the_interpreter() {
+        let source = "print_bool(print_int(1) == unit);
+            var u = { };
+            print_bool(print_int(7) == u);
+            fun nothing() {}
+            print_bool(nothing() != u)";
+        let output = compile_and_run(source, "");
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "1\ntrue\n7\ntrue\nfalse\n");
+        assert_eq!(stdout(&output), interpret(source));
+    }
+
+    #[test]
+    fn compiled_program_runs() {
+        let output = compile_and_run(
+            "var x = 0; while x < 10 do { x = x + 1 }; if x == 10 then x = 7 / -1 % 3",
+            "",
+        );
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn division_by_zero_fails_at_runtime() {
+        for source in ["var x = 0; 1 / x", "print_int(1); var x = 0; 1 % x"] {
+            let output = compile_and_run(source, "");
+            assert_eq!(output.status.code(), Some(1));
+            assert_eq!(
+                String::from_utf8_lossy(&output.stderr),
+                "division by zero\n"
+            );
+        }
+        let output = compile_and_run("print_int(7 / -1); print_int(7 % -1)", "");
+        assert_eq!(stdout(&output), "-7\n0\n");
+    }
+
+    #[test]
+    fn printing() {
+        let output = compile_and_run(
+            "print_int(0); print_int(-120); print_int(9223372036854775807);
+            print_int(-9223372036854775807 - 1); print_bool(1 < 2); print_bool(false)",
+            "",
+        );
+        assert!(output.status.success());
+        assert_eq!(
+            stdout(&output),
+            "0\n-120\n9223372036854775807\n-9223372036854775808\ntrue\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn reading() {
+        let output = compile_and_run(
+            "var sum = 0; var i = 0; while i < 5 do { sum = sum + read_int(); i = i + 1 }; print_int(sum)",
+            "1\n  -20 \n+300\r\n-9223372036854775808\n9223372036854775807",
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "280\n");
+    }
+
+    #[test]
+    fn invalid_input() {
+        for input in ["", "\n", "12a\n", "-\n", "9223372036854775808\n"] {
+            let output = compile_and_run("print_int(read_int())", input);
+            assert_eq!(output.status.code(), Some(1), "input {:?}", input);
+            assert_eq!(stdout(&output), "");
+            assert_eq!(output.stderr, b"read_int: invalid input\n");
+        }
+    }
+
+    #[test]
+    fn functions() {
+        let output = compile_and_run(
+            "fun fib(n: Int): Int { if n < 2 then return n; fib(n - 1) + fib(n - 2) }
+            fun is_even(n: Int): Bool { if n == 0 then true else is_odd(n - 1) }
+            fun is_odd(n: Int): Bool { if n == 0 then false else is_even(n - 1) }
+            fun sum(a: Int, b: Int, c: Int, d: Int, e: Int, f: Int, g: Int, h: Int): Int {
+                a + b + c + d + e + f + g * h
+            }
+            fun show(n: Int) { print_int(n); }
+            show(fib(read_int()));
+            print_bool(is_even(7));
+            show(sum(1, 2, 3, 4, 5, 6, 7, 8))",
+            "20\n",
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "6765\nfalse\n77\n");
+    }
+
+    #[test]
+    fn function_values() {
+        let output = compile_and_run(
+            "fun one(): Int { return 1 }
+            fun sum(a: Int, b: Int, c: Int, d: Int, e: Int, f: Int, g: Int): Int {
+                a + b + c + d + e + f + g
+            }
+            fun apply(n: Int): Int { var twice = sum; twice(n, n, n, n, n, n, n) }
+            var x = one;
+            print_int(x());
+            var f = print_int;
+            f(3);
+            x = read_int;
+            print_int(apply(x()));
+            print_bool(f == print_int)",
+            "2\n",
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "1\n3\n14\ntrue\n");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let output = compile_and_run(
+            "var i = 0;
+            var first_square = while true do {
+                i = i + 1;
+                if i % 2 == 1 then continue;
+                if i * i > 50 then break i;
+            };
+            print_int(first_square);
+            while true do { while true do break; break }",
+            "",
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "8\n");
+    }
+
+    #[test]
+    fn compiling_to_bytes() {
+        let program = compile_to_bytes("print_int(1)".to_string(), "test.scl").unwrap();
+        assert_eq!(&program[..4], b"\x7fELF");
+    }
+
+    #[test]
+    fn all_errors_are_reported() {
+        let errors = check_source_all(
+            "var x = 1 $ 2;\nfun f(a Int) { a }\n{ 1 + ; x }; @\nx = (1 2);\ny".to_string(),
+            "a.scl",
+        )
+        .unwrap_err();
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "unexpected character '$' at a.scl:1:11",
+                "expected ';', found '2' at a.scl:1:13",
+                "expected ':', found 'Int' at a.scl:2:9",
+                "expected an expression, found ';' at a.scl:3:7",
+                "unexpected character '@' at a.scl:3:14",
+                "expected ')', found '2' at a.scl:4:8",
+            ]
+        );
+        let errors = check_source_all("var x = 1;\nx + true".to_string(), "a.scl").unwrap_err();
+        assert!(matches!(errors[..], [CompileError::Type(_)]));
+        assert!(check_source_all("var x = 1; x".to_string(), "a.scl").is_ok());
+    }
+
+    #[test]
+    fn errors_before_code_generation() {
+        let output = Path::new("never-written");
+        assert!(matches!(
+            compile_source("1 $".to_string(), "a.scl", output),
+            Err(CompileError::Lex(_))
+        ));
+        assert!(matches!(
+            compile_source("1 +".to_string(), "a.scl", output),
+            Err(CompileError::Parse(_))
+        ));
+        assert!(matches!(
+            compile_source("1 + true".to_string(), "a.scl", output),
+            Err(CompileError::Type(_))
+        ));
+        assert!(!output.exists());
+    }
+}
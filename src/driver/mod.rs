@@ -0,0 +1,8 @@
+mod assemble;
+mod compile;
+
+pub use assemble::{assemble_and_link, ToolError};
+pub use compile::{
+    check_source, check_source_all, compile_file, compile_source, compile_to_bytes, format_source,
+    generate_program_asm, generate_program_ir, parse_source, CompileError,
+};
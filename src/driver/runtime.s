@@ -0,0 +1,194 @@
+    .section .text
+
+# Entry point of compiled programs. The stack is 16-byte aligned here, so
+# main gets the alignment the calling convention expects.
+    .global _start
+    .type _start, @function
+_start:
+    callq main
+    movq %rax, %rdi
+    movq $60, %rax
+    syscall
+
+# print_int(value): writes the value in decimal followed by a newline
+    .global print_int
+    .type print_int, @function
+print_int:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $32, %rsp
+    # Digits are written backwards from the end of the buffer
+    leaq -1(%rbp), %rsi
+    movb $10, (%rsi)
+    movq %rdi, %rax
+    movq $10, %rcx
+.Lprint_int_digit:
+    # Remainders have the sign of the value, which avoids negating i64::MIN
+    cqto
+    idivq %rcx
+    testq %rdx, %rdx
+    jns .Lprint_int_store
+    negq %rdx
+.Lprint_int_store:
+    addq $48, %rdx
+    decq %rsi
+    movb %dl, (%rsi)
+    testq %rax, %rax
+    jne .Lprint_int_digit
+    testq %rdi, %rdi
+    jns .Lprint_int_write
+    decq %rsi
+    movb $45, (%rsi)
+.Lprint_int_write:
+    movq %rbp, %rdx
+    subq %rsi, %rdx
+    movq $1, %rax
+    movq $1, %rdi
+    syscall
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+
+# print_bool(value): writes true or false followed by a newline
+    .global print_bool
+    .type print_bool, @function
+print_bool:
+    testq %rdi, %rdi
+    je .Lprint_bool_false
+    leaq .Ltrue_text(%rip), %rsi
+    movq $5, %rdx
+    jmp .Lprint_bool_write
+.Lprint_bool_false:
+    leaq .Lfalse_text(%rip), %rsi
+    movq $6, %rdx
+.Lprint_bool_write:
+    movq $1, %rax
+    movq $1, %rdi
+    syscall
+    ret
+
+# Reads one byte from stdin into %rax, -1 at the end of input
+.Lread_byte:
+    subq $8, %rsp
+    xorq %rax, %rax
+    xorq %rdi, %rdi
+    movq %rsp, %rsi
+    movq $1, %rdx
+    syscall
+    cmpq $1, %rax
+    jne .Lread_byte_eof
+    movzbq (%rsp), %rax
+    addq $8, %rsp
+    ret
+.Lread_byte_eof:
+    movq $-1, %rax
+    addq $8, %rsp
+    ret
+
+# read_int(): reads a line holding an optionally signed integer surrounded by
+# spaces or tabs. Exits the program with status 1 on invalid input.
+    .global read_int
+    .type read_int, @function
+read_int:
+    pushq %rbp
+    movq %rsp, %rbp
+    # %rbx holds the negated value so far, which also fits i64::MIN
+    pushq %rbx
+    # %r12 is 1 for negative numbers
+    pushq %r12
+    # %r13 counts digits
+    pushq %r13
+    subq $8, %rsp
+    xorq %rbx, %rbx
+    xorq %r12, %r12
+    xorq %r13, %r13
+.Lread_int_leading:
+    callq .Lread_byte
+    cmpq $32, %rax
+    je .Lread_int_leading
+    cmpq $9, %rax
+    je .Lread_int_leading
+    cmpq $45, %rax
+    jne .Lread_int_plus
+    movq $1, %r12
+    jmp .Lread_int_next
+.Lread_int_plus:
+    cmpq $43, %rax
+    jne .Lread_int_digit
+.Lread_int_next:
+    callq .Lread_byte
+.Lread_int_digit:
+    cmpq $48, %rax
+    jl .Lread_int_trailing
+    cmpq $57, %rax
+    jg .Lread_int_trailing
+    subq $48, %rax
+    imulq $10, %rbx
+    jo .Lread_int_invalid
+    subq %rax, %rbx
+    jo .Lread_int_invalid
+    incq %r13
+    jmp .Lread_int_next
+.Lread_int_trailing:
+    cmpq $32, %rax
+    je .Lread_int_skip
+    cmpq $9, %rax
+    je .Lread_int_skip
+    cmpq $13, %rax
+    je .Lread_int_skip
+    cmpq $10, %rax
+    je .Lread_int_done
+    cmpq $-1, %rax
+    je .Lread_int_done
+    jmp .Lread_int_invalid
+.Lread_int_skip:
+    callq .Lread_byte
+    jmp .Lread_int_trailing
+.Lread_int_done:
+    testq %r13, %r13
+    je .Lread_int_invalid
+    movq %rbx, %rax
+    testq %r12, %r12
+    jne .Lread_int_return
+    negq %rax
+    jo .Lread_int_invalid
+.Lread_int_return:
+    addq $8, %rsp
+    popq %r13
+    popq %r12
+    popq %rbx
+    popq %rbp
+    ret
+.Lread_int_invalid:
+    movq $1, %rax
+    movq $2, %rdi
+    leaq .Linvalid_input_text(%rip), %rsi
+    movq $24, %rdx
+    syscall
+    movq $60, %rax
+    movq $1, %rdi
+    syscall
+
+# Jumped to by division and remainder with a zero divisor. Exits the program
+# with status 1 like the interpreter's error.
+    .global _division_by_zero
+    .type _division_by_zero, @function
+_division_by_zero:
+    movq $1, %rax
+    movq $2, %rdi
+    leaq .Ldivision_by_zero_text(%rip), %rsi
+    movq $17, %rdx
+    syscall
+    movq $60, %rax
+    movq $1, %rdi
+    syscall
+
+    .section .rodata
+.Ltrue_text:
+    .ascii "true\n"
+.Lfalse_text:
+    .ascii "false\n"
+.Linvalid_input_text:
+    .ascii "read_int: invalid input\n"
+.Ldivision_by_zero_text:
+    .ascii "division by zero\n"
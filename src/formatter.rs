@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+use std::mem;
+
+use crate::parser::{
+    BinaryOperator, Expression, ExpressionKind, FunctionDefinition, Module, UnaryOperator,
+};
+use crate::tokenizer::{Punctuation, Token, TokenType};
+
+const INDENT: &str = "    ";
+
+// Binding levels matching the parser, from assignment at 0 to the prefix
+// operators
+fn binary_precedence(operator: BinaryOperator) -> u8 {
+    match operator {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::Equal | BinaryOperator::NotEqual => 3,
+        BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual => 4,
+        BinaryOperator::Add | BinaryOperator::Subtract => 5,
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Remainder => 6,
+    }
+}
+
+const UNARY_PRECEDENCE: u8 = 7;
+
+// What follows an expression in the output. Expressions without an end of
+// their own, like `while` and `return`, would take in a following operator,
+// and an `if` without `else` would take a following `else`.
+#[derive(Clone, Copy, PartialEq)]
+enum Next {
+    Nothing,
+    Else,
+    Operator,
+}
+
+enum Item<'a> {
+    Function(&'a FunctionDefinition),
+    // The flag is false for the result of a sequence
+    Expression(&'a Expression, bool),
+}
+
+impl Item<'_> {
+    fn offset(&self) -> usize {
+        match self {
+            Item::Function(function) => function.location.offset(),
+            Item::Expression(expression, _) => expression.location.offset(),
+        }
+    }
+}
+
+fn block_items(block: &Expression) -> Vec<Item<'_>> {
+    match &block.kind {
+        ExpressionKind::Block {
+            expressions,
+            result,
+        } => expressions
+            .iter()
+            .map(|expression| Item::Expression(expression, true))
+            .chain(
+                result
+                    .iter()
+                    .map(|expression| Item::Expression(expression, false)),
+            )
+            .collect(),
+        _ => vec![Item::Expression(block, false)],
+    }
+}
+
+// Whether the parser lets the expression go without a semicolon, which is
+// the case for the statements ending with `}`
+fn ends_with_block(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Block { .. } => true,
+        ExpressionKind::If {
+            else_branch: Some(else_branch),
+            ..
+        } => ends_with_block(else_branch),
+        ExpressionKind::If { then_branch, .. } => ends_with_block(then_branch),
+        ExpressionKind::While { body, .. } => ends_with_block(body),
+        _ => false,
+    }
+}
+
+// Without a semicolon in between, a leading `-` would continue the
+// statement before as a subtraction
+fn starts_with_minus(item: &Item) -> bool {
+    let mut expression = match item {
+        Item::Function(_) => return false,
+        Item::Expression(expression, _) => *expression,
+    };
+    loop {
+        match &expression.kind {
+            ExpressionKind::UnaryOp {
+                operator: UnaryOperator::Negate,
+                ..
+            } => return true,
+            ExpressionKind::BinaryOp { left, .. } => expression = left,
+            _ => return false,
+        }
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    // Comments still to be written, the next one last
+    comments: Vec<&'a Token>,
+    // Offsets of the `}` closing the blocks, by the offset of their `{`
+    block_ends: HashMap<usize, usize>,
+    lines: Vec<String>,
+    line: String,
+    indent: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(tokens: &'a [Token], source: &'a str) -> Formatter<'a> {
+        let mut comments = Vec::new();
+        let mut block_ends = HashMap::new();
+        let mut open_blocks = Vec::new();
+        for token in tokens {
+            let offset = token.location().offset();
+            match token.token_type() {
+                TokenType::Comment => comments.push(token),
+                TokenType::Punctuation(Punctuation::CurlyOpen) => open_blocks.push(offset),
+                TokenType::Punctuation(Punctuation::CurlyClose) => {
+                    if let Some(start) = open_blocks.pop() {
+                        block_ends.insert(start, offset);
+                    }
+                }
+                _ => {}
+            }
+        }
+        comments.reverse();
+        Formatter {
+            source,
+            comments,
+            block_ends,
+            lines: Vec::new(),
+            line: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line.is_empty() {
+            self.line = INDENT.repeat(self.indent);
+        }
+        self.line += text;
+    }
+
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(mem::take(&mut self.line));
+        }
+    }
+
+    // Ends the line and leaves an empty one, except at the start of the
+    // output or of a block
+    fn blank_line(&mut self) {
+        self.end_line();
+        if let Some(last) = self.lines.last() {
+            if !last.is_empty() && !last.ends_with('{') {
+                self.lines.push(String::new());
+            }
+        }
+    }
+
+    // Empty lines in the source are kept, though several become one
+    fn blank_line_before(&self, offset: usize) -> bool {
+        let before = &self.source[..offset];
+        before[before.trim_end().len()..].matches('\n').count() > 1
+    }
+
+    fn comment_before(&self, offset: usize) -> bool {
+        self.comments
+            .last()
+            .is_some_and(|comment| comment.location().offset() < offset)
+    }
+
+    // Writes the comments before an offset. A comment after code stays at
+    // the end of the current line, the others get lines of their own. With
+    // `separate` the first comment on its own line gets an empty line before
+    // it, and the return value tells whether that is still to be done.
+    fn comments_before(&mut self, offset: usize, mut separate: bool) -> bool {
+        while self.comment_before(offset) {
+            let comment = self.comments.pop().unwrap();
+            let start = comment.location().offset();
+            let line_start = self.source[..start]
+                .rfind('\n')
+                .map_or(0, |index| index + 1);
+            let after_code = !self.source[line_start..start].trim().is_empty();
+            if after_code && !self.line.is_empty() {
+                self.line.push(' ');
+            } else if separate || self.blank_line_before(start) {
+                self.blank_line();
+                separate = false;
+            } else {
+                self.end_line();
+            }
+            self.write(comment.value().trim_end());
+            self.end_line();
+        }
+        separate
+    }
+
+    // Statements of a block or of the top level, one per line
+    fn items(&mut self, items: &[Item]) {
+        let mut previous_function = false;
+        for (index, item) in items.iter().enumerate() {
+            let start = item.offset();
+            let function = matches!(item, Item::Function(_));
+            // Functions are always set apart by empty lines
+            let separate = index > 0 && (function || previous_function);
+            if self.comments_before(start, separate) || self.blank_line_before(start) {
+                self.blank_line();
+            } else {
+                self.end_line();
+            }
+            match item {
+                Item::Function(function) => self.function(function),
+                Item::Expression(expression, statement) => {
+                    self.expression(expression, 0, Next::Nothing);
+                    let next = items.get(index + 1);
+                    let semicolon = match next {
+                        Some(next) => !ends_with_block(expression) || starts_with_minus(next),
+                        // The last semicolon decides the value of the sequence
+                        None => *statement,
+                    };
+                    if semicolon {
+                        self.write(";");
+                    }
+                }
+            }
+            previous_function = function;
+        }
+    }
+
+    fn block(&mut self, block: &Expression) {
+        let end = self
+            .block_ends
+            .get(&block.location.offset())
+            .copied()
+            .unwrap_or(self.source.len());
+        let items = block_items(block);
+        if items.is_empty() && !self.comment_before(end) {
+            self.write("{}");
+            return;
+        }
+        self.write("{");
+        self.indent += 1;
+        self.items(&items);
+        self.comments_before(end, false);
+        self.indent -= 1;
+        self.end_line();
+        self.write("}");
+    }
+
+    fn function(&mut self, function: &FunctionDefinition) {
+        self.write(&format!("fun {}(", function.name));
+        let parameters: Vec<String> = function
+            .parameters
+            .iter()
+            .map(|parameter| parameter.to_string())
+            .collect();
+        self.write(&parameters.join(", "));
+        self.write(")");
+        if let Some(return_type) = &function.return_type {
+            self.write(&format!(": {}", return_type));
+        }
+        self.write(" ");
+        self.block(&function.body);
+    }
+
+    // Parentheses are only written where leaving them out would change the
+    // meaning
+    fn expression(&mut self, expression: &Expression, precedence: u8, next: Next) {
+        let parenthesize = match &expression.kind {
+            ExpressionKind::BinaryOp { operator, .. } => binary_precedence(*operator) < precedence,
+            ExpressionKind::Assignment { .. } => precedence > 0,
+            ExpressionKind::If {
+                else_branch: None, ..
+            } => next != Next::Nothing,
+            ExpressionKind::If { .. }
+            | ExpressionKind::While { .. }
+            | ExpressionKind::Return { .. }
+            | ExpressionKind::Break { .. } => next == Next::Operator,
+            _ => false,
+        };
+        if parenthesize {
+            self.write("(");
+            self.expression(expression, 0, Next::Nothing);
+            self.write(")");
+            return;
+        }
+        match &expression.kind {
+            ExpressionKind::IntLiteral(value) => self.write(&value.to_string()),
+            ExpressionKind::BoolLiteral(value) => self.write(&value.to_string()),
+            ExpressionKind::UnitLiteral => self.write("unit"),
+            ExpressionKind::Identifier(name) => self.write(name),
+            ExpressionKind::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let precedence = binary_precedence(*operator);
+                self.expression(left, precedence, Next::Operator);
+                self.write(&format!(" {} ", operator));
+                self.expression(right, precedence + 1, next);
+            }
+            ExpressionKind::UnaryOp { operator, operand } => {
+                self.write(operator.symbol());
+                if *operator == UnaryOperator::Not {
+                    self.write(" ");
+                }
+                self.expression(operand, UNARY_PRECEDENCE, next);
+            }
+            ExpressionKind::Assignment { name, value } => {
+                self.write(&format!("{} = ", name));
+                self.expression(value, 0, next);
+            }
+            ExpressionKind::Call { name, arguments } => {
+                self.write(&format!("{}(", name));
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        self.write(", ");
+                    }
+                    self.expression(argument, 0, Next::Nothing);
+                }
+                self.write(")");
+            }
+            ExpressionKind::Block { .. } => self.block(expression),
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.write("if ");
+                self.expression(condition, 0, Next::Nothing);
+                self.write(" then ");
+                match else_branch {
+                    Some(else_branch) => {
+                        self.expression(then_branch, 0, Next::Else);
+                        self.write(" else ");
+                        self.expression(else_branch, 0, next);
+                    }
+                    None => self.expression(then_branch, 0, next),
+                }
+            }
+            ExpressionKind::While { condition, body } => {
+                self.write("while ");
+                self.expression(condition, 0, Next::Nothing);
+                self.write(" do ");
+                self.expression(body, 0, next);
+            }
+            ExpressionKind::VarDeclaration {
+                name,
+                constant,
+                type_annotation,
+                initializer,
+            } => {
+                let keyword = if *constant { "const" } else { "var" };
+                self.write(&format!("{} {}", keyword, name));
+                if let Some(type_annotation) = type_annotation {
+                    self.write(&format!(": {}", type_annotation));
+                }
+                self.write(" = ");
+                self.expression(initializer, 0, next);
+            }
+            ExpressionKind::Return { value } => self.keyword_with_value("return", value, next),
+            ExpressionKind::Break { value } => self.keyword_with_value("break", value, next),
+            ExpressionKind::Continue => self.write("continue"),
+        }
+    }
+
+    fn keyword_with_value(&mut self, keyword: &str, value: &Option<Box<Expression>>, next: Next) {
+        self.write(keyword);
+        if let Some(value) = value {
+            self.write(" ");
+            self.expression(value, 0, next);
+        }
+    }
+}
+
+// Prints a module in the canonical style. The body must be the block of
+// top-level expressions as returned by `parse_input`, and `tokens` include
+// the comments, which are kept next to the code they were next to.
+pub fn format_module(module: &Module, tokens: &[Token], source: &str) -> String {
+    let mut items = block_items(&module.body);
+    items.extend(module.functions.iter().map(Item::Function));
+    items.sort_by_key(Item::offset);
+    let mut formatter = Formatter::new(tokens, source);
+    formatter.items(&items);
+    formatter.comments_before(usize::MAX, false);
+    formatter.end_line();
+    // A file without code or comments stays empty
+    if formatter.lines.is_empty() {
+        return String::new();
+    }
+    let mut output = formatter.lines.join("\n");
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::{format_source, parse_source};
+
+    fn format(source: &str) -> String {
+        format_source(source.to_string(), "a.scl").unwrap()
+    }
+
+    // Formatting keeps the meaning and formatting again changes nothing.
+    // Locations differ, so the trees are compared as text.
+    fn assert_stable(source: &str) {
+        let formatted = format(source);
+        assert_eq!(format(&formatted), formatted);
+        let tree = |source: &str| {
+            parse_source(source.to_string(), "a.scl")
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(tree(&formatted), tree(source));
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(
+            format(
+                "fun f(a:Int,b:Bool):Int{if b then{a}else{-a}}var x=f(1,true);while x<3 do x=x+1;x"
+            ),
+            "fun f(a: Int, b: Bool): Int {
+    if b then {
+        a
+    } else {
+        -a
+    }
+}
+
+var x = f(1, true);
+while x < 3 do x = x + 1;
+x
+"
+        );
+        assert_eq!(format("{}"), "{}\n");
+        assert_eq!(
+            format("const  y : Bool = not true;"),
+            "const y: Bool = not true;\n"
+        );
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            format("((1 + 2)) * (3 * 4) - (5 - 6)"),
+            "(1 + 2) * (3 * 4) - (5 - 6)\n"
+        );
+        assert_eq!(format("(a = b) or (c and d)"), "(a = b) or c and d\n");
+        assert_eq!(format("-(-x) + (not y)"), "--x + not y\n");
+        assert_eq!(
+            format("(if a then 1 else 2) + (while b do c) * (return)"),
+            "(if a then 1 else 2) + (while b do c) * return\n"
+        );
+        assert_eq!(
+            format("1 + (if a then 2 else 3)"),
+            "1 + if a then 2 else 3\n"
+        );
+        assert_eq!(
+            format("if a then (if b then c) else d"),
+            "if a then (if b then c) else d\n"
+        );
+        assert_eq!(format("f((return), (x = 1))"), "f(return, x = 1)\n");
+        for source in [
+            "((1 + 2)) * (3 * 4) - (5 - 6)",
+            "(a = b) or (c and d)",
+            "-(-x) + (not y)",
+            "(if a then 1 else 2) + (while b do c) * (return)",
+            "if a then (if b then c) else d",
+            "if a then (while b do if c then d) else e",
+        ] {
+            assert_stable(source);
+        }
+    }
+
+    #[test]
+    fn semicolons() {
+        assert_eq!(
+            format("while a do { b }; c; { d };"),
+            "while a do {\n    b\n}\nc;\n{\n    d\n};\n"
+        );
+        // Would be a subtraction without the semicolon
+        assert_eq!(format("{ a }; -b"), "{\n    a\n};\n-b\n");
+        assert_stable("{ a }; -b");
+        assert_stable("if a then { b } else { c } d");
+    }
+
+    #[test]
+    fn comments() {
+        let source = "# first
+
+// about f
+fun f() { // opening
+    g(1, // inside a call
+      2);
+
+    // before the end
+}
+var x = { // only a comment
+};   // after x
+// last";
+        assert_eq!(
+            format(source),
+            "# first
+
+// about f
+fun f() { // opening
+    g(1, 2); // inside a call
+
+    // before the end
+}
+
+var x = { // only a comment
+}; // after x
+// last
+"
+        );
+        assert_stable(source);
+        assert_eq!(
+            format("fun f() {}\n// about g\nfun g() {}"),
+            "fun f() {}\n\n// about g\nfun g() {}\n"
+        );
+        assert_eq!(format("a;\n\n\n\nb"), "a;\n\nb\n");
+        assert_eq!(format("// nothing\n\n  # yet"), "// nothing\n\n# yet\n");
+        assert_eq!(format(""), "");
+        assert_eq!(format(" \n\n"), "");
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use super::value::{BuiltinFunction, Value};
+
+// Returns the next line of input, None at the end of input
+pub type InputSource = Rc<dyn Fn() -> Option<String>>;
+pub type OutputSink = Rc<dyn Fn(&str)>;
+
+// Accepts the same input as read_int of the compiled runtime: an optionally
+// signed integer surrounded by spaces or tabs
+fn parse_int(line: Option<String>) -> Result<Value, String> {
+    let line = line.ok_or("read_int: unexpected end of input")?;
+    let text = line.trim_matches(|c| c == ' ' || c == '\t' || c == '\r' || c == '\n');
+    text.parse()
+        .map(Value::Int)
+        .map_err(|_| format!("read_int: invalid integer '{}'", text))
+}
+
+pub fn io_builtins(input: InputSource, output: OutputSink) -> Vec<BuiltinFunction> {
+    let print_output = output.clone();
+    vec![
+        BuiltinFunction::new(
+            "print_int",
+            1,
+            Rc::new(move |arguments: &[Value]| {
+                print_output(&format!("{}\n", arguments[0]));
+                Ok(Value::Unit)
+            }),
+        ),
+        BuiltinFunction::new(
+            "print_bool",
+            1,
+            Rc::new(move |arguments: &[Value]| {
+                output(&format!("{}\n", arguments[0]));
+                Ok(Value::Unit)
+            }),
+        ),
+        BuiltinFunction::new(
+            "read_int",
+            0,
+            Rc::new(move |_: &[Value]| parse_int(input())),
+        ),
+    ]
+}
+
+pub fn stdin_lines() -> InputSource {
+    Rc::new(|| {
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    })
+}
+
+// Builtins reading from stdin and writing to stdout
+pub fn standard_builtins() -> Vec<BuiltinFunction> {
+    io_builtins(
+        stdin_lines(),
+        Rc::new(|text: &str| {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(text.as_bytes());
+            let _ = stdout.flush();
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::builtins::*;
+
+    #[test]
+    fn parsing_input() {
+        assert_eq!(parse_int(Some(" -12\t\n".to_string())), Ok(Value::Int(-12)));
+        assert_eq!(parse_int(Some("+7\r\n".to_string())), Ok(Value::Int(7)));
+        assert_eq!(
+            parse_int(Some("-9223372036854775808".to_string())),
+            Ok(Value::Int(i64::MIN))
+        );
+        assert_eq!(
+            parse_int(Some("12a\n".to_string())),
+            Err("read_int: invalid integer '12a'".to_string())
+        );
+        assert_eq!(
+            parse_int(Some("9223372036854775808".to_string())),
+            Err("read_int: invalid integer '9223372036854775808'".to_string())
+        );
+        assert_eq!(
+            parse_int(None),
+            Err("read_int: unexpected end of input".to_string())
+        );
+    }
+}
@@ -0,0 +1,699 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::arithmetic;
+use crate::diagnostics::{Diagnostic, Span};
+use crate::parser::{
+    BinaryOperator, Expression, ExpressionKind, FunctionDefinition, Module, UnaryOperator,
+};
+use crate::symbol_table::SymbolTable;
+use crate::tokenizer::Location;
+
+use super::builtins::standard_builtins;
+use super::value::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    span: Span,
+    message: String,
+}
+
+impl RuntimeError {
+    fn new(span: &Span, message: String) -> RuntimeError {
+        RuntimeError {
+            span: span.clone(),
+            message,
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        self.span.location()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::at(self.message.clone(), self.span.clone())
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.location())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+fn expect_int(value: Value, span: &Span) -> Result<i64, RuntimeError> {
+    match value {
+        Value::Int(value) => Ok(value),
+        other => Err(RuntimeError::new(
+            span,
+            format!("expected an Int, got '{}'", other),
+        )),
+    }
+}
+
+fn expect_bool(value: Value, span: &Span) -> Result<bool, RuntimeError> {
+    match value {
+        Value::Bool(value) => Ok(value),
+        other => Err(RuntimeError::new(
+            span,
+            format!("expected a Bool, got '{}'", other),
+        )),
+    }
+}
+
+// Why evaluation stopped before producing a value. `Return` travels up to
+// the enclosing function call, `Break` and `Continue` to the enclosing loop.
+enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+    Break(Value),
+    Continue,
+}
+
+impl Unwind {
+    // For control flow that escaped the construct it belongs to
+    fn into_error(self, span: &Span) -> RuntimeError {
+        let message = match self {
+            Unwind::Error(error) => return error,
+            Unwind::Return(_) => "'return' outside of a function",
+            Unwind::Break(_) => "'break' outside of a loop",
+            Unwind::Continue => "'continue' outside of a loop",
+        };
+        RuntimeError::new(span, message.to_string())
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+// Evaluates expressions directly from the AST. The outermost scope of the
+// symbol table holds the builtins and survives between calls to `evaluate`.
+#[derive(Clone)]
+pub struct Interpreter {
+    symbols: SymbolTable<Value>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    // Starts with the builtins reading stdin and writing stdout
+    pub fn new() -> Interpreter {
+        let mut interpreter = Interpreter {
+            symbols: SymbolTable::new(),
+        };
+        for function in standard_builtins() {
+            interpreter.define_builtin(function);
+        }
+        interpreter
+    }
+
+    pub fn define_builtin(&mut self, function: BuiltinFunction) {
+        let name = function.name().to_string();
+        match self.symbols.lookup_mut(&name) {
+            Some(value) => *value = Value::Builtin(function),
+            None => {
+                self.symbols.declare(&name, Value::Builtin(function));
+            }
+        }
+    }
+
+    pub fn evaluate(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        self.eval(expression)
+            .map_err(|unwind| unwind.into_error(&expression.span()))
+    }
+
+    // Defines the functions of the module and runs its top-level
+    // expressions in a scope of their own
+    pub fn evaluate_module(&mut self, module: &Module) -> Result<Value, RuntimeError> {
+        self.define_functions(&module.functions);
+        self.symbols.push_scope();
+        let value = self.evaluate(&module.body);
+        self.symbols.pop_scope();
+        value
+    }
+
+    // Runs one input of the REPL, parsed with `parse_input`, keeping the
+    // variables it declares in the outermost scope for the following inputs
+    pub fn evaluate_input(&mut self, module: &Module) -> Result<Value, RuntimeError> {
+        self.define_functions(&module.functions);
+        let value = match &module.body.kind {
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => self.evaluate_block(expressions, result),
+            _ => self.eval(&module.body),
+        };
+        value.map_err(|unwind| unwind.into_error(&module.body.span()))
+    }
+
+    fn define_functions(&mut self, functions: &[FunctionDefinition]) {
+        for function in functions {
+            let value = Value::Function(Rc::new(function.clone()));
+            match self.symbols.lookup_mut(&function.name) {
+                Some(existing) => *existing = value,
+                None => {
+                    self.symbols.declare(&function.name, value);
+                }
+            }
+        }
+    }
+
+    fn eval(&mut self, expression: &Expression) -> Result<Value, Unwind> {
+        let span = &expression.span();
+        match &expression.kind {
+            ExpressionKind::IntLiteral(value) => Ok(Value::Int(*value)),
+            ExpressionKind::BoolLiteral(value) => Ok(Value::Bool(*value)),
+            ExpressionKind::UnitLiteral => Ok(Value::Unit),
+            ExpressionKind::Identifier(name) => match self.symbols.lookup(name) {
+                Some(value) => Ok(value.clone()),
+                None => {
+                    Err(RuntimeError::new(span, format!("undefined variable '{}'", name)).into())
+                }
+            },
+            ExpressionKind::UnaryOp { operator, operand } => {
+                let value = self.eval(operand)?;
+                match operator {
+                    UnaryOperator::Negate => {
+                        Ok(Value::Int(expect_int(value, span)?.wrapping_neg()))
+                    }
+                    UnaryOperator::Not => Ok(Value::Bool(!expect_bool(value, span)?)),
+                }
+            }
+            ExpressionKind::BinaryOp {
+                left,
+                operator,
+                right,
+            } => self.evaluate_binary_op(left, *operator, right, span),
+            ExpressionKind::Assignment { name, value } => {
+                let value = self.eval(value)?;
+                match self.symbols.lookup_mut(name) {
+                    Some(variable) => {
+                        *variable = value.clone();
+                        Ok(value)
+                    }
+                    None => Err(
+                        RuntimeError::new(span, format!("undefined variable '{}'", name)).into(),
+                    ),
+                }
+            }
+            ExpressionKind::Call { name, arguments } => self.evaluate_call(name, arguments, span),
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => {
+                self.symbols.push_scope();
+                let value = self.evaluate_block(expressions, result);
+                self.symbols.pop_scope();
+                value
+            }
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.eval(condition)?;
+                if expect_bool(condition, span)? {
+                    let value = self.eval(then_branch)?;
+                    match else_branch {
+                        Some(_) => Ok(value),
+                        None => Ok(Value::Unit),
+                    }
+                } else {
+                    match else_branch {
+                        Some(else_branch) => self.eval(else_branch),
+                        None => Ok(Value::Unit),
+                    }
+                }
+            }
+            ExpressionKind::While { condition, body } => {
+                loop {
+                    let condition = self.eval(condition)?;
+                    if !expect_bool(condition, span)? {
+                        break;
+                    }
+                    match self.eval(body) {
+                        Err(Unwind::Break(value)) => return Ok(value),
+                        Ok(_) | Err(Unwind::Continue) => {}
+                        Err(other) => return Err(other),
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            ExpressionKind::VarDeclaration {
+                name, initializer, ..
+            } => {
+                let value = self.eval(initializer)?;
+                if !self.symbols.declare(name, value) {
+                    return Err(RuntimeError::new(
+                        span,
+                        format!("variable '{}' is already declared in this scope", name),
+                    )
+                    .into());
+                }
+                Ok(Value::Unit)
+            }
+            ExpressionKind::Return { value } => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Unit,
+                };
+                Err(Unwind::Return(value))
+            }
+            ExpressionKind::Break { value } => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Unit,
+                };
+                Err(Unwind::Break(value))
+            }
+            ExpressionKind::Continue => Err(Unwind::Continue),
+        }
+    }
+
+    fn evaluate_block(
+        &mut self,
+        expressions: &[Expression],
+        result: &Option<Box<Expression>>,
+    ) -> Result<Value, Unwind> {
+        for expression in expressions {
+            self.eval(expression)?;
+        }
+        match result {
+            Some(result) => self.eval(result),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn evaluate_binary_op(
+        &mut self,
+        left: &Expression,
+        operator: BinaryOperator,
+        right: &Expression,
+        span: &Span,
+    ) -> Result<Value, Unwind> {
+        let left = self.eval(left)?;
+        // The right operand of `and` and `or` is only evaluated when needed
+        match operator {
+            BinaryOperator::And => {
+                if !expect_bool(left, span)? {
+                    return Ok(Value::Bool(false));
+                }
+                let right = self.eval(right)?;
+                return Ok(Value::Bool(expect_bool(right, span)?));
+            }
+            BinaryOperator::Or => {
+                if expect_bool(left, span)? {
+                    return Ok(Value::Bool(true));
+                }
+                let right = self.eval(right)?;
+                return Ok(Value::Bool(expect_bool(right, span)?));
+            }
+            _ => {}
+        }
+        let right = self.eval(right)?;
+        match operator {
+            BinaryOperator::Equal => return Ok(Value::Bool(left == right)),
+            BinaryOperator::NotEqual => return Ok(Value::Bool(left != right)),
+            _ => {}
+        }
+        let left = expect_int(left, span)?;
+        let right = expect_int(right, span)?;
+        let division_result = |result: Result<i64, arithmetic::DivisionByZero>| {
+            result
+                .map(Value::Int)
+                .map_err(|error| RuntimeError::new(span, error.to_string()).into())
+        };
+        match operator {
+            BinaryOperator::Add => Ok(Value::Int(left.wrapping_add(right))),
+            BinaryOperator::Subtract => Ok(Value::Int(left.wrapping_sub(right))),
+            BinaryOperator::Multiply => Ok(Value::Int(left.wrapping_mul(right))),
+            BinaryOperator::Divide => division_result(arithmetic::divide(left, right)),
+            BinaryOperator::Remainder => division_result(arithmetic::remainder(left, right)),
+            BinaryOperator::Less => Ok(Value::Bool(left < right)),
+            BinaryOperator::LessOrEqual => Ok(Value::Bool(left <= right)),
+            BinaryOperator::Greater => Ok(Value::Bool(left > right)),
+            BinaryOperator::GreaterOrEqual => Ok(Value::Bool(left >= right)),
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::And
+            | BinaryOperator::Or => unreachable!(),
+        }
+    }
+
+    fn evaluate_call(
+        &mut self,
+        name: &str,
+        arguments: &[Expression],
+        span: &Span,
+    ) -> Result<Value, Unwind> {
+        let (arity, function) = match self.symbols.lookup(name) {
+            Some(Value::Builtin(function)) => (function.arity(), Value::Builtin(function.clone())),
+            Some(Value::Function(function)) => {
+                (function.parameters.len(), Value::Function(function.clone()))
+            }
+            Some(other) => {
+                return Err(RuntimeError::new(
+                    span,
+                    format!("'{}' is not a function, it is '{}'", name, other),
+                )
+                .into())
+            }
+            None => {
+                return Err(
+                    RuntimeError::new(span, format!("undefined function '{}'", name)).into(),
+                )
+            }
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                span,
+                format!(
+                    "function '{}' takes {} arguments but {} were given",
+                    name,
+                    arity,
+                    arguments.len()
+                ),
+            )
+            .into());
+        }
+        let mut values: Vec<Value> = Vec::new();
+        for argument in arguments {
+            values.push(self.eval(argument)?);
+        }
+        match function {
+            Value::Builtin(function) => function
+                .call(&values)
+                .map_err(|message| RuntimeError::new(span, message).into()),
+            Value::Function(function) => self.call_function(&function, values),
+            _ => unreachable!(),
+        }
+    }
+
+    // The body only sees the globals and its parameters
+    fn call_function(
+        &mut self,
+        function: &FunctionDefinition,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Unwind> {
+        let outer_scopes = self.symbols.take_inner_scopes();
+        self.symbols.push_scope();
+        for (parameter, value) in function.parameters.iter().zip(arguments) {
+            self.symbols.declare(&parameter.name, value);
+        }
+        let result = self.eval(&function.body);
+        self.symbols.restore_inner_scopes(outer_scopes);
+        match result {
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(unwind @ (Unwind::Break(_) | Unwind::Continue)) => {
+                Err(unwind.into_error(&function.body.span()).into())
+            }
+            other => other,
+        }
+    }
+}
+
+pub fn interpret(expression: &Expression) -> Result<Value, RuntimeError> {
+    Interpreter::new().evaluate(expression)
+}
+
+pub fn interpret_module(module: &Module) -> Result<Value, RuntimeError> {
+    Interpreter::new().evaluate_module(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::interpreter::builtins::io_builtins;
+    use crate::interpreter::interpret::*;
+    use crate::parser::{parse, parse_module};
+    use crate::tokenizer::{tokenize, UNNAMED_SOURCE};
+
+    fn run(source: &str) -> Result<Value, RuntimeError> {
+        let expression = parse(tokenize(source.to_string()).unwrap()).unwrap();
+        interpret(&expression)
+    }
+
+    fn run_ok(source: &str) -> Value {
+        run(source).unwrap()
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(run_ok("12"), Value::Int(12));
+        assert_eq!(run_ok("true"), Value::Bool(true));
+        assert_eq!(run_ok("unit"), Value::Unit);
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(run_ok("1 + 2 * 3"), Value::Int(7));
+        assert_eq!(run_ok("(1 + 2) * 3"), Value::Int(9));
+        assert_eq!(run_ok("10 - 2 - 3"), Value::Int(5));
+        assert_eq!(run_ok("-7 / 2"), Value::Int(-3));
+        assert_eq!(run_ok("-7 % 2"), Value::Int(-1));
+        assert_eq!(run_ok("- -3"), Value::Int(3));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let error = run("1 / 0").unwrap_err();
+        assert_eq!(error.message(), "division by zero");
+        assert_eq!(
+            error.diagnostic().render("1 / 0", false),
+            "error: division by zero
+ --> <input>:1:1
+  |
+1 | 1 / 0
+  | ^^^^^
+"
+        );
+        assert!(run("1 % (2 - 2)").is_err());
+    }
+
+    #[test]
+    fn comparisons_and_booleans() {
+        assert_eq!(run_ok("1 < 2"), Value::Bool(true));
+        assert_eq!(run_ok("2 <= 1"), Value::Bool(false));
+        assert_eq!(run_ok("1 == 1 and 2 != 3"), Value::Bool(true));
+        assert_eq!(run_ok("true == false"), Value::Bool(false));
+        assert_eq!(run_ok("not true or 3 >= 3"), Value::Bool(true));
+        assert_eq!(run_ok("unit == unit"), Value::Bool(true));
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        // The right operand would fail if it were evaluated
+        assert_eq!(run_ok("false and 1 / 0 == 1"), Value::Bool(false));
+        assert_eq!(run_ok("true or 1 / 0 == 1"), Value::Bool(true));
+        assert_eq!(
+            run_ok("var x = 0; false and { x = 1; true }; x"),
+            Value::Int(0)
+        );
+        assert_eq!(
+            run_ok("var x = 0; true and { x = 1; true }; x"),
+            Value::Int(1)
+        );
+        assert!(run("true and 1 / 0 == 1").is_err());
+    }
+
+    #[test]
+    fn variables_and_scopes() {
+        assert_eq!(run_ok("var x = 1; x = x + 1; x"), Value::Int(2));
+        assert_eq!(run_ok("var x = 1; { var x = 2; x }"), Value::Int(2));
+        assert_eq!(run_ok("var x = 1; { var x = 2; }; x"), Value::Int(1));
+        assert_eq!(run_ok("var x = 1; { x = 2; }; x"), Value::Int(2));
+        assert_eq!(run_ok("var x = 1; var y = x = 3; x"), Value::Int(3));
+        assert!(run("{ var x = 1; }; x").is_err());
+        assert!(run("var x = 1; var x = 2").is_err());
+        assert!(run("y = 1").is_err());
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(run_ok("{ 1; 2 }"), Value::Int(2));
+        assert_eq!(run_ok("{ 1; 2; }"), Value::Unit);
+        assert_eq!(run_ok("{ }"), Value::Unit);
+    }
+
+    #[test]
+    fn if_expressions() {
+        assert_eq!(run_ok("if 1 < 2 then 3 else 4"), Value::Int(3));
+        assert_eq!(run_ok("if 1 > 2 then 3 else 4"), Value::Int(4));
+        assert_eq!(run_ok("if false then 3"), Value::Unit);
+        assert_eq!(run_ok("if true then 3"), Value::Unit);
+        assert_eq!(
+            run_ok("var x = 5; if x < 0 then 0 else if x < 10 then 1 else 2"),
+            Value::Int(1)
+        );
+        assert!(run("if 1 then 2 else 3").is_err());
+    }
+
+    #[test]
+    fn while_loops() {
+        assert_eq!(
+            run_ok("var i = 0; var sum = 0; while i < 5 do { i = i + 1; sum = sum + i; } sum"),
+            Value::Int(15)
+        );
+        assert_eq!(run_ok("while false do 1"), Value::Unit);
+    }
+
+    #[test]
+    fn builtin_calls() {
+        let printed: Rc<RefCell<Vec<Value>>> = Rc::new(RefCell::new(Vec::new()));
+        let sink = printed.clone();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_builtin(BuiltinFunction::new(
+            "record",
+            1,
+            Rc::new(move |arguments: &[Value]| {
+                sink.borrow_mut().push(arguments[0].clone());
+                Ok(Value::Unit)
+            }),
+        ));
+        interpreter.define_builtin(BuiltinFunction::new(
+            "max",
+            2,
+            Rc::new(|arguments: &[Value]| match (&arguments[0], &arguments[1]) {
+                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a.max(b))),
+                _ => Err("max expects integers".to_string()),
+            }),
+        ));
+        let source = "var i = 0; while i < 3 do { record(max(i, 1)); i = i + 1; } max(i, 2)";
+        let expression = parse(tokenize(source.to_string()).unwrap()).unwrap();
+        assert_eq!(interpreter.evaluate(&expression), Ok(Value::Int(3)));
+        assert_eq!(
+            *printed.borrow(),
+            vec![Value::Int(1), Value::Int(1), Value::Int(2)]
+        );
+
+        let expression = parse(tokenize("max(true, 1)".to_string()).unwrap()).unwrap();
+        let error = interpreter.evaluate(&expression).unwrap_err();
+        assert_eq!(error.message(), "max expects integers");
+        let expression = parse(tokenize("max(1)".to_string()).unwrap()).unwrap();
+        assert!(interpreter.evaluate(&expression).is_err());
+    }
+
+    #[test]
+    fn calling_undefined_function() {
+        assert_eq!(run("f(1)").unwrap_err().message(), "undefined function 'f'");
+        assert!(run("var f = 1; f(1)").is_err());
+    }
+
+    #[test]
+    fn io_builtins_read_and_print() {
+        let input = Rc::new(RefCell::new(vec!["5\n".to_string(), " -3 \n".to_string()]));
+        let output = Rc::new(RefCell::new(String::new()));
+        let source = input.clone();
+        let sink = output.clone();
+        let mut interpreter = Interpreter::new();
+        for function in io_builtins(
+            Rc::new(move || {
+                let mut lines = source.borrow_mut();
+                (!lines.is_empty()).then(|| lines.remove(0))
+            }),
+            Rc::new(move |text: &str| sink.borrow_mut().push_str(text)),
+        ) {
+            interpreter.define_builtin(function);
+        }
+        let expression = parse(
+            tokenize("print_int(read_int() * read_int()); print_bool(true)".to_string()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(interpreter.evaluate(&expression), Ok(Value::Unit));
+        assert_eq!(*output.borrow(), "-15\ntrue\n");
+
+        let expression = parse(tokenize("read_int()".to_string()).unwrap()).unwrap();
+        let error = interpreter.evaluate(&expression).unwrap_err();
+        assert_eq!(error.message(), "read_int: unexpected end of input");
+    }
+
+    fn run_module(source: &str) -> Result<Value, RuntimeError> {
+        let module = parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE).unwrap();
+        interpret_module(&module)
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            run_module(
+                "fun fib(n: Int): Int { if n < 2 then return n; fib(n - 1) + fib(n - 2) }
+                fun is_even(n: Int): Bool { if n == 0 then true else is_odd(n - 1) }
+                fun is_odd(n: Int): Bool { if n == 0 then false else is_even(n - 1) }
+                var n = 10;
+                if is_even(n) then fib(n) else 0"
+            ),
+            Ok(Value::Int(55))
+        );
+    }
+
+    #[test]
+    fn functions_have_their_own_scope() {
+        let source = "fun f(n: Int): Int { var x = n; while true do { return x * 2 } } \
+            var x = 1; f(5) + x";
+        assert_eq!(run_module(source), Ok(Value::Int(11)));
+        assert_eq!(
+            run_module("fun f() { x } var x = 1; f()")
+                .unwrap_err()
+                .message(),
+            "undefined variable 'x'"
+        );
+    }
+
+    #[test]
+    fn return_outside_function() {
+        assert_eq!(
+            run("1; return 2; 3").unwrap_err().message(),
+            "'return' outside of a function"
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(
+            run_ok(
+                "var i = 0; var sum = 0;
+                while true do {
+                    i = i + 1;
+                    if i > 10 then break;
+                    if i % 2 == 0 then continue;
+                    sum = sum + i;
+                }
+                sum"
+            ),
+            Value::Int(25)
+        );
+        assert_eq!(
+            run_ok("var i = 0; while true do { i = i + 1; if i * i > 50 then break i }"),
+            Value::Int(8)
+        );
+        assert_eq!(
+            run_ok("var n = 0; while n < 3 do { n = n + 1; while true do break; }; n"),
+            Value::Int(3)
+        );
+        assert_eq!(
+            run("break").unwrap_err().message(),
+            "'break' outside of a loop"
+        );
+        assert_eq!(
+            run_module("fun f() { continue } while true do f()")
+                .unwrap_err()
+                .message(),
+            "'continue' outside of a loop"
+        );
+    }
+}
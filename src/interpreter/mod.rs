@@ -0,0 +1,6 @@
+mod builtins;
+mod interpret;
+mod value;
+pub use builtins::{io_builtins, standard_builtins, stdin_lines, InputSource, OutputSink};
+pub use interpret::{interpret, interpret_module, Interpreter, RuntimeError};
+pub use value::*;
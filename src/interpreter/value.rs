@@ -0,0 +1,70 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::FunctionDefinition;
+
+pub type BuiltinImplementation = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+// Function implemented in Rust and callable from SCL code
+#[derive(Clone)]
+pub struct BuiltinFunction {
+    name: String,
+    arity: usize,
+    implementation: BuiltinImplementation,
+}
+
+impl BuiltinFunction {
+    pub fn new(name: &str, arity: usize, implementation: BuiltinImplementation) -> BuiltinFunction {
+        BuiltinFunction {
+            name: name.to_string(),
+            arity,
+            implementation,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, String> {
+        (self.implementation)(arguments)
+    }
+}
+
+impl fmt::Debug for BuiltinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BuiltinFunction({})", self.name)
+    }
+}
+
+// Builtins are identified by their name
+impl PartialEq for BuiltinFunction {
+    fn eq(&self, other: &BuiltinFunction) -> bool {
+        self.name == other.name
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Unit,
+    Builtin(BuiltinFunction),
+    Function(Rc<FunctionDefinition>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Unit => write!(f, "unit"),
+            Value::Builtin(function) => write!(f, "<builtin {}>", function.name()),
+            Value::Function(function) => write!(f, "<function {}>", function.name),
+        }
+    }
+}
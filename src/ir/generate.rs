@@ -0,0 +1,799 @@
+use std::collections::HashSet;
+
+use crate::builtins::builtin_types;
+use crate::parser::{
+    BinaryOperator, Expression, ExpressionKind, FunctionDefinition, Module, UnaryOperator,
+};
+use crate::symbol_table::SymbolTable;
+use crate::tokenizer::Location;
+use crate::typechecker::Type;
+
+use super::instruction::*;
+
+// IR variable holding the unit value
+pub const UNIT_VAR: &str = "unit";
+
+pub fn unary_operator_name(operator: UnaryOperator) -> String {
+    format!("unary_{}", operator.symbol())
+}
+
+// Hands out names that are unique within a function
+struct Namer {
+    used: HashSet<String>,
+    counter: usize,
+}
+
+impl Namer {
+    fn new() -> Namer {
+        Namer {
+            used: HashSet::new(),
+            counter: 0,
+        }
+    }
+
+    fn reserve(&mut self, name: &str) {
+        self.used.insert(name.to_string());
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        loop {
+            self.counter += 1;
+            let name = format!("{}{}", prefix, self.counter);
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    // Keeps the given name when it is still free
+    fn named(&mut self, name: &str) -> String {
+        if self.used.insert(name.to_string()) {
+            return name.to_string();
+        }
+        self.fresh(&format!("{}_", name))
+    }
+}
+
+// Jump targets of a loop being generated. `result` receives the values of
+// `break` when the loop has a value.
+struct Loop {
+    start_label: String,
+    end_label: String,
+    result: Option<IrVar>,
+}
+
+// Lowers a type-checked AST to a linear list of instructions
+pub struct IrGenerator {
+    namer: Namer,
+    symbols: SymbolTable<IrVar>,
+    // Functions, which are called by name and only stored in variables when
+    // used as values
+    functions: HashSet<IrVar>,
+    instructions: Vec<Instruction>,
+    // Enclosing loops, innermost last
+    loops: Vec<Loop>,
+}
+
+impl Default for IrGenerator {
+    fn default() -> Self {
+        IrGenerator::new()
+    }
+}
+
+impl IrGenerator {
+    // Operators are predeclared as globals named after their symbol, builtin
+    // functions after their name
+    pub fn new() -> IrGenerator {
+        let mut generator = IrGenerator {
+            namer: Namer::new(),
+            symbols: SymbolTable::new(),
+            functions: HashSet::new(),
+            instructions: Vec::new(),
+            loops: Vec::new(),
+        };
+        generator.declare_global(UNIT_VAR);
+        for operator in [
+            BinaryOperator::Add,
+            BinaryOperator::Subtract,
+            BinaryOperator::Multiply,
+            BinaryOperator::Divide,
+            BinaryOperator::Remainder,
+            BinaryOperator::Equal,
+            BinaryOperator::NotEqual,
+            BinaryOperator::Less,
+            BinaryOperator::LessOrEqual,
+            BinaryOperator::Greater,
+            BinaryOperator::GreaterOrEqual,
+        ] {
+            generator.declare_global(operator.symbol());
+        }
+        for operator in [UnaryOperator::Negate, UnaryOperator::Not] {
+            generator.declare_global(&unary_operator_name(operator));
+        }
+        for (name, _) in builtin_types() {
+            generator.declare_function(name);
+        }
+        generator
+    }
+
+    pub fn declare_global(&mut self, name: &str) {
+        self.namer.reserve(name);
+        self.symbols.declare(name, IrVar::new(name));
+    }
+
+    pub fn declare_function(&mut self, name: &str) {
+        self.declare_global(name);
+        self.functions.insert(IrVar::new(name));
+    }
+
+    // Generates a function without parameters that evaluates the expression
+    // and returns nothing
+    pub fn generate(mut self, name: &str, expression: &Expression) -> IrFunction {
+        self.visit(expression);
+        self.emit(
+            InstructionKind::Return { value: None },
+            &expression.location,
+        );
+        IrFunction {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            instructions: self.instructions,
+        }
+    }
+
+    pub fn generate_function(mut self, function: &FunctionDefinition) -> IrFunction {
+        self.symbols.push_scope();
+        let mut parameters = Vec::new();
+        for parameter in &function.parameters {
+            let var = IrVar::new(&self.namer.named(&parameter.name));
+            self.symbols.declare(&parameter.name, var.clone());
+            parameters.push(var);
+        }
+        let result = self.visit(&function.body);
+        self.emit_return(result, &function.body.location);
+        IrFunction {
+            name: function.name.clone(),
+            parameters,
+            instructions: self.instructions,
+        }
+    }
+
+    fn emit_return(&mut self, value: IrVar, location: &Location) {
+        let value = if value.name == UNIT_VAR {
+            None
+        } else {
+            Some(value)
+        };
+        self.emit(InstructionKind::Return { value }, location);
+    }
+
+    fn emit(&mut self, kind: InstructionKind, location: &Location) {
+        self.instructions
+            .push(Instruction::new(kind, location.clone()));
+    }
+
+    fn new_var(&mut self) -> IrVar {
+        IrVar::new(&self.namer.fresh("x"))
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.namer.fresh(prefix)
+    }
+
+    fn lookup(&self, name: &str) -> IrVar {
+        match self.symbols.lookup(name) {
+            Some(var) => var.clone(),
+            None => panic!("undefined name '{}', was the AST type checked?", name),
+        }
+    }
+
+    fn unit(&self) -> IrVar {
+        IrVar::new(UNIT_VAR)
+    }
+
+    fn emit_label(&mut self, name: &str, location: &Location) {
+        self.emit(
+            InstructionKind::Label {
+                name: name.to_string(),
+            },
+            location,
+        );
+    }
+
+    fn emit_jump(&mut self, label: &str, location: &Location) {
+        self.emit(
+            InstructionKind::Jump {
+                label: label.to_string(),
+            },
+            location,
+        );
+    }
+
+    // Returns the IR variable holding the value of the expression
+    fn visit(&mut self, expression: &Expression) -> IrVar {
+        let location = &expression.location;
+        match &expression.kind {
+            ExpressionKind::IntLiteral(value) => {
+                let dest = self.new_var();
+                self.emit(
+                    InstructionKind::LoadIntConst {
+                        value: *value,
+                        dest: dest.clone(),
+                    },
+                    location,
+                );
+                dest
+            }
+            ExpressionKind::BoolLiteral(value) => {
+                let dest = self.new_var();
+                self.emit(
+                    InstructionKind::LoadBoolConst {
+                        value: *value,
+                        dest: dest.clone(),
+                    },
+                    location,
+                );
+                dest
+            }
+            ExpressionKind::UnitLiteral => self.unit(),
+            ExpressionKind::Identifier(name) => {
+                let var = self.lookup(name);
+                if !self.functions.contains(&var) {
+                    return var;
+                }
+                // A function used as a value
+                let dest = self.new_var();
+                self.emit(
+                    InstructionKind::LoadFunction {
+                        function: var,
+                        dest: dest.clone(),
+                    },
+                    location,
+                );
+                dest
+            }
+            ExpressionKind::UnaryOp { operator, operand } => {
+                let operand = self.visit(operand);
+                let function = self.lookup(&unary_operator_name(*operator));
+                self.emit_call(function, vec![operand], location)
+            }
+            ExpressionKind::BinaryOp {
+                left,
+                operator: BinaryOperator::And,
+                right,
+            } => self.visit_short_circuit(left, right, false, location),
+            ExpressionKind::BinaryOp {
+                left,
+                operator: BinaryOperator::Or,
+                right,
+            } => self.visit_short_circuit(left, right, true, location),
+            // Unit values carry no data and all of them are equal, so the
+            // result does not depend on what the operands hold
+            ExpressionKind::BinaryOp {
+                left,
+                operator: operator @ (BinaryOperator::Equal | BinaryOperator::NotEqual),
+                right,
+            } if left.ty == Some(Type::Unit) || right.ty == Some(Type::Unit) => {
+                self.visit(left);
+                self.visit(right);
+                let dest = self.new_var();
+                self.emit(
+                    InstructionKind::LoadBoolConst {
+                        value: *operator == BinaryOperator::Equal,
+                        dest: dest.clone(),
+                    },
+                    location,
+                );
+                dest
+            }
+            ExpressionKind::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.visit(left);
+                let right = self.visit(right);
+                let function = self.lookup(operator.symbol());
+                self.emit_call(function, vec![left, right], location)
+            }
+            ExpressionKind::Assignment { name, value } => {
+                let source = self.visit(value);
+                let dest = self.lookup(name);
+                self.emit(
+                    InstructionKind::Copy {
+                        source,
+                        dest: dest.clone(),
+                    },
+                    location,
+                );
+                dest
+            }
+            ExpressionKind::Call { name, arguments } => {
+                let arguments: Vec<IrVar> = arguments
+                    .iter()
+                    .map(|argument| self.visit(argument))
+                    .collect();
+                let function = self.lookup(name);
+                self.emit_call(function, arguments, location)
+            }
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => {
+                self.symbols.push_scope();
+                for expression in expressions {
+                    self.visit(expression);
+                }
+                let var = match result {
+                    Some(result) => self.visit(result),
+                    None => self.unit(),
+                };
+                self.symbols.pop_scope();
+                var
+            }
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch: None,
+            } => {
+                let then_label = self.new_label("then");
+                let end_label = self.new_label("if_end");
+                let condition = self.visit(condition);
+                self.emit(
+                    InstructionKind::CondJump {
+                        condition,
+                        then_label: then_label.clone(),
+                        else_label: end_label.clone(),
+                    },
+                    location,
+                );
+                self.emit_label(&then_label, location);
+                self.visit(then_branch);
+                self.emit_label(&end_label, location);
+                self.unit()
+            }
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => {
+                let then_label = self.new_label("then");
+                let else_label = self.new_label("else");
+                let end_label = self.new_label("if_end");
+                let dest = self.new_var();
+                let condition = self.visit(condition);
+                self.emit(
+                    InstructionKind::CondJump {
+                        condition,
+                        then_label: then_label.clone(),
+                        else_label: else_label.clone(),
+                    },
+                    location,
+                );
+                self.emit_label(&then_label, location);
+                let then_var = self.visit(then_branch);
+                self.emit_copy(then_var, dest.clone(), location);
+                self.emit_jump(&end_label, location);
+                self.emit_label(&else_label, location);
+                let else_var = self.visit(else_branch);
+                self.emit_copy(else_var, dest.clone(), location);
+                self.emit_label(&end_label, location);
+                dest
+            }
+            ExpressionKind::While { condition, body } => {
+                let start_label = self.new_label("while_start");
+                let body_label = self.new_label("while_body");
+                let end_label = self.new_label("while_end");
+                // Only loops that break with a value have a type other than Unit
+                let result = match expression.ty {
+                    Some(Type::Unit) | None => None,
+                    Some(_) => Some(self.new_var()),
+                };
+                self.emit_label(&start_label, location);
+                let condition = self.visit(condition);
+                self.emit(
+                    InstructionKind::CondJump {
+                        condition,
+                        then_label: body_label.clone(),
+                        else_label: end_label.clone(),
+                    },
+                    location,
+                );
+                self.emit_label(&body_label, location);
+                self.loops.push(Loop {
+                    start_label: start_label.clone(),
+                    end_label: end_label.clone(),
+                    result: result.clone(),
+                });
+                self.visit(body);
+                self.loops.pop();
+                self.emit_jump(&start_label, location);
+                self.emit_label(&end_label, location);
+                result.unwrap_or_else(|| self.unit())
+            }
+            ExpressionKind::VarDeclaration {
+                name, initializer, ..
+            } => {
+                let source = self.visit(initializer);
+                let dest = IrVar::new(&self.namer.named(name));
+                self.emit_copy(source, dest.clone(), location);
+                self.symbols.declare(name, dest);
+                self.unit()
+            }
+            ExpressionKind::Return { value } => {
+                let value = match value {
+                    Some(value) => self.visit(value),
+                    None => self.unit(),
+                };
+                self.emit_return(value, location);
+                self.unit()
+            }
+            ExpressionKind::Break { value } => {
+                let value = value.as_ref().map(|value| self.visit(value));
+                let target = self.loops.last().expect("'break' outside of a loop");
+                let end_label = target.end_label.clone();
+                if let (Some(source), Some(dest)) = (value, target.result.clone()) {
+                    self.emit_copy(source, dest, location);
+                }
+                self.emit_jump(&end_label, location);
+                self.unit()
+            }
+            ExpressionKind::Continue => {
+                let target = self.loops.last().expect("'continue' outside of a loop");
+                let start_label = target.start_label.clone();
+                self.emit_jump(&start_label, location);
+                self.unit()
+            }
+        }
+    }
+
+    fn emit_copy(&mut self, source: IrVar, dest: IrVar, location: &Location) {
+        self.emit(InstructionKind::Copy { source, dest }, location);
+    }
+
+    fn emit_call(&mut self, function: IrVar, arguments: Vec<IrVar>, location: &Location) -> IrVar {
+        let dest = self.new_var();
+        self.emit(
+            InstructionKind::Call {
+                function,
+                arguments,
+                dest: dest.clone(),
+            },
+            location,
+        );
+        dest
+    }
+
+    // `and` skips the right operand when the left one is false and `or`
+    // when it is true. `skip_value` is the result in the skipped case.
+    fn visit_short_circuit(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        skip_value: bool,
+        location: &Location,
+    ) -> IrVar {
+        let prefix = if skip_value { "or" } else { "and" };
+        let right_label = self.new_label(&format!("{}_right", prefix));
+        let skip_label = self.new_label(&format!("{}_skip", prefix));
+        let end_label = self.new_label(&format!("{}_end", prefix));
+        let dest = self.new_var();
+        let left = self.visit(left);
+        let (then_label, else_label) = if skip_value {
+            (skip_label.clone(), right_label.clone())
+        } else {
+            (right_label.clone(), skip_label.clone())
+        };
+        self.emit(
+            InstructionKind::CondJump {
+                condition: left,
+                then_label,
+                else_label,
+            },
+            location,
+        );
+        self.emit_label(&right_label, location);
+        let right = self.visit(right);
+        self.emit_copy(right, dest.clone(), location);
+        self.emit_jump(&end_label, location);
+        self.emit_label(&skip_label, location);
+        self.emit(
+            InstructionKind::LoadBoolConst {
+                value: skip_value,
+                dest: dest.clone(),
+            },
+            location,
+        );
+        self.emit_label(&end_label, location);
+        dest
+    }
+}
+
+pub fn generate_ir(expression: &Expression) -> IrFunction {
+    IrGenerator::new().generate("main", expression)
+}
+
+// Every function gets its own generator, with the names of all functions
+// declared as globals. The top-level expressions become `main`.
+pub fn generate_module_ir(module: &Module) -> Vec<IrFunction> {
+    let new_generator = || {
+        let mut generator = IrGenerator::new();
+        for function in &module.functions {
+            generator.declare_function(&function.name);
+        }
+        generator
+    };
+    let mut functions: Vec<IrFunction> = module
+        .functions
+        .iter()
+        .map(|function| new_generator().generate_function(function))
+        .collect();
+    functions.push(new_generator().generate("main", &module.body));
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::generate::*;
+    use crate::parser::{parse, parse_module};
+    use crate::tokenizer::{tokenize, UNNAMED_SOURCE};
+    use crate::typechecker::{typecheck_module, Type, TypeChecker};
+
+    fn ir_dump(source: &str) -> String {
+        let mut expression = parse(tokenize(source.to_string()).unwrap()).unwrap();
+        let mut type_checker = TypeChecker::new();
+        type_checker.declare_global("f", Type::function(vec![Type::Int], Type::Int));
+        type_checker.check(&mut expression).unwrap();
+        let mut generator = IrGenerator::new();
+        generator.declare_function("f");
+        generator.generate("main", &expression).to_string()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            ir_dump("1 + 2 * 3"),
+            "main():
+    LoadIntConst(1, x1)
+    LoadIntConst(2, x2)
+    LoadIntConst(3, x3)
+    Call(*, [x2, x3], x4)
+    Call(+, [x1, x4], x5)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn unary_operators_and_calls() {
+        assert_eq!(
+            ir_dump("-f(1) % 2"),
+            "main():
+    LoadIntConst(1, x1)
+    Call(f, [x1], x2)
+    Call(unary_-, [x2], x3)
+    LoadIntConst(2, x4)
+    Call(%, [x3, x4], x5)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn function_values() {
+        assert_eq!(
+            ir_dump("var g = f; g(1)"),
+            "main():
+    LoadFunction(f, x1)
+    Copy(x1, g)
+    LoadIntConst(1, x2)
+    Call(g, [x2], x3)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn unit_comparisons() {
+        assert_eq!(
+            ir_dump("f(1) == 1; {} != unit"),
+            "main():
+    LoadIntConst(1, x1)
+    Call(f, [x1], x2)
+    LoadIntConst(1, x3)
+    Call(==, [x2, x3], x4)
+    LoadBoolConst(false, x5)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn variables_and_shadowing() {
+        assert_eq!(
+            ir_dump("var x = 1; { var x = true; x = false }; x = 2"),
+            "main():
+    LoadIntConst(1, x1)
+    Copy(x1, x)
+    LoadBoolConst(true, x2)
+    Copy(x2, x_3)
+    LoadBoolConst(false, x4)
+    Copy(x4, x_3)
+    LoadIntConst(2, x5)
+    Copy(x5, x)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn if_then_else() {
+        assert_eq!(
+            ir_dump("if true then 1 else 2"),
+            "main():
+    LoadBoolConst(true, x5)
+    CondJump(x5, then1, else2)
+Label(then1)
+    LoadIntConst(1, x6)
+    Copy(x6, x4)
+    Jump(if_end3)
+Label(else2)
+    LoadIntConst(2, x7)
+    Copy(x7, x4)
+Label(if_end3)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn if_without_else() {
+        assert_eq!(
+            ir_dump("if false then f(1)"),
+            "main():
+    LoadBoolConst(false, x3)
+    CondJump(x3, then1, if_end2)
+Label(then1)
+    LoadIntConst(1, x4)
+    Call(f, [x4], x5)
+Label(if_end2)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn while_loop() {
+        assert_eq!(
+            ir_dump("var i = 0; while i < 3 do i = i + 1"),
+            "main():
+    LoadIntConst(0, x1)
+    Copy(x1, i)
+Label(while_start2)
+    LoadIntConst(3, x5)
+    Call(<, [i, x5], x6)
+    CondJump(x6, while_body3, while_end4)
+Label(while_body3)
+    LoadIntConst(1, x7)
+    Call(+, [i, x7], x8)
+    Copy(x8, i)
+    Jump(while_start2)
+Label(while_end4)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn short_circuit_operators() {
+        assert_eq!(
+            ir_dump("true and false"),
+            "main():
+    LoadBoolConst(true, x5)
+    CondJump(x5, and_right1, and_skip2)
+Label(and_right1)
+    LoadBoolConst(false, x6)
+    Copy(x6, x4)
+    Jump(and_end3)
+Label(and_skip2)
+    LoadBoolConst(false, x4)
+Label(and_end3)
+    Return()
+"
+        );
+        assert_eq!(
+            ir_dump("true or false"),
+            "main():
+    LoadBoolConst(true, x5)
+    CondJump(x5, or_skip2, or_right1)
+Label(or_right1)
+    LoadBoolConst(false, x6)
+    Copy(x6, x4)
+    Jump(or_end3)
+Label(or_skip2)
+    LoadBoolConst(true, x4)
+Label(or_end3)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn user_names_do_not_clash_with_temporaries() {
+        assert_eq!(
+            ir_dump("var x1 = 5; x1 + 1"),
+            "main():
+    LoadIntConst(5, x1)
+    Copy(x1, x1_2)
+    LoadIntConst(1, x3)
+    Call(+, [x1_2, x3], x4)
+    Return()
+"
+        );
+    }
+
+    fn module_ir_dump(source: &str) -> String {
+        let mut module =
+            parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE).unwrap();
+        typecheck_module(&mut module).unwrap();
+        generate_module_ir(&module)
+            .iter()
+            .map(|function| function.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            module_ir_dump(
+                "fun add(a: Int, x1: Int): Int { return a + x1; }
+                fun nothing(flag: Bool) { if flag then return; }
+                nothing(add(1, 2) < 3)"
+            ),
+            "add(a, x1):
+    Call(+, [a, x1], x2)
+    Return(x2)
+    Return()
+nothing(flag):
+    CondJump(flag, then1, if_end2)
+Label(then1)
+    Return()
+Label(if_end2)
+    Return()
+main():
+    LoadIntConst(1, x1)
+    LoadIntConst(2, x2)
+    Call(add, [x1, x2], x3)
+    LoadIntConst(3, x4)
+    Call(<, [x3, x4], x5)
+    Call(nothing, [x5], x6)
+    Return()
+"
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(
+            ir_dump("var x = while true do { if false then continue; break 1 }"),
+            "main():
+Label(while_start1)
+    LoadBoolConst(true, x5)
+    CondJump(x5, while_body2, while_end3)
+Label(while_body2)
+    LoadBoolConst(false, x8)
+    CondJump(x8, then6, if_end7)
+Label(then6)
+    Jump(while_start1)
+Label(if_end7)
+    LoadIntConst(1, x9)
+    Copy(x9, x4)
+    Jump(while_end3)
+    Jump(while_start1)
+Label(while_end3)
+    Copy(x4, x)
+    Return()
+"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use crate::tokenizer::Location;
+
+// Named variable of the intermediate representation. Operators and builtin
+// functions are also IR variables, named after the operator or function.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct IrVar {
+    pub name: String,
+}
+
+impl IrVar {
+    pub fn new(name: &str) -> IrVar {
+        IrVar {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for IrVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstructionKind {
+    LoadIntConst {
+        value: i64,
+        dest: IrVar,
+    },
+    LoadBoolConst {
+        value: bool,
+        dest: IrVar,
+    },
+    Copy {
+        source: IrVar,
+        dest: IrVar,
+    },
+    // Stores the address of a global function, so that it can be called
+    // through a variable
+    LoadFunction {
+        function: IrVar,
+        dest: IrVar,
+    },
+    // Calls a global function by name, or the function whose address is in
+    // a variable
+    Call {
+        function: IrVar,
+        arguments: Vec<IrVar>,
+        dest: IrVar,
+    },
+    Jump {
+        label: String,
+    },
+    CondJump {
+        condition: IrVar,
+        then_label: String,
+        else_label: String,
+    },
+    Label {
+        name: String,
+    },
+    // Leaves the function, returning nothing for unit
+    Return {
+        value: Option<IrVar>,
+    },
+}
+
+// The location is the source expression the instruction was generated from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub kind: InstructionKind,
+    pub location: Location,
+}
+
+impl Instruction {
+    pub fn new(kind: InstructionKind, location: Location) -> Instruction {
+        Instruction { kind, location }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            InstructionKind::LoadIntConst { value, dest } => {
+                write!(f, "LoadIntConst({}, {})", value, dest)
+            }
+            InstructionKind::LoadBoolConst { value, dest } => {
+                write!(f, "LoadBoolConst({}, {})", value, dest)
+            }
+            InstructionKind::Copy { source, dest } => write!(f, "Copy({}, {})", source, dest),
+            InstructionKind::LoadFunction { function, dest } => {
+                write!(f, "LoadFunction({}, {})", function, dest)
+            }
+            InstructionKind::Call {
+                function,
+                arguments,
+                dest,
+            } => {
+                write!(f, "Call({}, [", function)?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, "], {})", dest)
+            }
+            InstructionKind::Jump { label } => write!(f, "Jump({})", label),
+            InstructionKind::CondJump {
+                condition,
+                then_label,
+                else_label,
+            } => write!(f, "CondJump({}, {}, {})", condition, then_label, else_label),
+            InstructionKind::Label { name } => write!(f, "Label({})", name),
+            InstructionKind::Return { value } => match value {
+                Some(value) => write!(f, "Return({})", value),
+                None => write!(f, "Return()"),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IrFunction {
+    pub name: String,
+    pub parameters: Vec<IrVar>,
+    pub instructions: Vec<Instruction>,
+}
+
+// Textual dump with one instruction per line, meant to be stable so that the
+// IR of test programs can be snapshotted and diffed
+impl fmt::Display for IrFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", parameter)?;
+        }
+        writeln!(f, "):")?;
+        for instruction in &self.instructions {
+            match instruction.kind {
+                InstructionKind::Label { .. } => writeln!(f, "{}", instruction)?,
+                _ => writeln!(f, "    {}", instruction)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+mod generate;
+mod instruction;
+pub use generate::{generate_ir, generate_module_ir, IrGenerator};
+pub use instruction::*;
@@ -0,0 +1,16 @@
+pub mod arithmetic;
+pub mod asm_generator;
+pub mod builtins;
+pub mod cli;
+pub mod diagnostics;
+pub mod driver;
+pub mod formatter;
+pub mod interpreter;
+pub mod ir;
+pub mod lsp;
+pub mod parser;
+pub mod repl;
+pub mod server;
+pub mod symbol_table;
+pub mod tokenizer;
+pub mod typechecker;
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use crate::builtins::builtin_types;
+use crate::diagnostics::Diagnostic;
+use crate::parser::{parse_module_recovering, Expression, ExpressionKind, Module, ParseError};
+use crate::symbol_table::SymbolTable;
+use crate::tokenizer::{tokenize_recovering, LexError, Location, Token, TokenType};
+use crate::typechecker::{function_type, typecheck_module, Type};
+
+// A variable, parameter or function declared in the document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    // The token naming it
+    pub token: Token,
+    // None when type checking did not get that far
+    pub ty: Option<Type>,
+}
+
+// A top-level function as listed in the outline of the document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub ty: Option<Type>,
+    pub start: Location,
+    // Right after the closing curly bracket of the body
+    pub end: Location,
+    pub name_token: Token,
+}
+
+// The direct subexpressions, in source order
+fn children(expression: &Expression) -> Vec<&Expression> {
+    match &expression.kind {
+        ExpressionKind::IntLiteral(_)
+        | ExpressionKind::BoolLiteral(_)
+        | ExpressionKind::UnitLiteral
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Continue => Vec::new(),
+        ExpressionKind::BinaryOp { left, right, .. } => vec![left, right],
+        ExpressionKind::UnaryOp { operand, .. } => vec![operand],
+        ExpressionKind::Assignment { value, .. } => vec![value],
+        ExpressionKind::Call { arguments, .. } => arguments.iter().collect(),
+        ExpressionKind::Block {
+            expressions,
+            result,
+        } => expressions.iter().chain(result.as_deref()).collect(),
+        ExpressionKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => [condition, then_branch]
+            .into_iter()
+            .map(|expression| expression.as_ref())
+            .chain(else_branch.as_deref())
+            .collect(),
+        ExpressionKind::While { condition, body } => vec![condition, body],
+        ExpressionKind::VarDeclaration { initializer, .. } => vec![initializer],
+        ExpressionKind::Return { value } | ExpressionKind::Break { value } => {
+            value.as_deref().into_iter().collect()
+        }
+    }
+}
+
+// Links every name used in an expression to its declaration, following the
+// same scoping rules as the type checker
+struct Resolver<'a> {
+    tokens: &'a [Token],
+    scopes: SymbolTable<usize>,
+    declarations: Vec<Declaration>,
+    // Keyed by the offset of the name, None for builtins and undefined names
+    references: HashMap<usize, Option<usize>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(tokens: &'a [Token]) -> Resolver<'a> {
+        Resolver {
+            tokens,
+            scopes: SymbolTable::new(),
+            declarations: Vec::new(),
+            references: HashMap::new(),
+        }
+    }
+
+    // Declarations start with `fun` or `var`, so the name is found among the
+    // tokens that follow
+    fn declare(&mut self, name: &str, location: &Location, ty: Option<Type>) {
+        let token = self
+            .tokens
+            .iter()
+            .find(|token| token.location().offset() >= location.offset() && token.value() == name);
+        if let Some(token) = token {
+            self.declarations.push(Declaration {
+                name: name.to_string(),
+                token: token.clone(),
+                ty,
+            });
+            self.scopes.declare(name, self.declarations.len() - 1);
+        }
+    }
+
+    fn reference(&mut self, name: &str, location: &Location) {
+        let declaration = self.scopes.lookup(name).copied();
+        self.references.insert(location.offset(), declaration);
+    }
+
+    fn resolve_module(&mut self, module: &Module) {
+        for function in &module.functions {
+            self.declare(
+                &function.name,
+                &function.location,
+                function_type(function).ok(),
+            );
+        }
+        for function in &module.functions {
+            self.scopes.push_scope();
+            for parameter in &function.parameters {
+                self.declare(
+                    &parameter.name,
+                    &parameter.location,
+                    Type::from_name(&parameter.type_annotation.name),
+                );
+            }
+            self.visit(&function.body);
+            self.scopes.pop_scope();
+        }
+        self.scopes.push_scope();
+        self.visit(&module.body);
+        self.scopes.pop_scope();
+    }
+
+    fn visit(&mut self, expression: &Expression) {
+        let location = &expression.location;
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => self.reference(name, location),
+            ExpressionKind::Assignment { name, value } => {
+                self.visit(value);
+                self.reference(name, location);
+            }
+            ExpressionKind::Call { name, arguments } => {
+                self.reference(name, location);
+                for argument in arguments {
+                    self.visit(argument);
+                }
+            }
+            ExpressionKind::Block { .. } => {
+                self.scopes.push_scope();
+                for child in children(expression) {
+                    self.visit(child);
+                }
+                self.scopes.pop_scope();
+            }
+            ExpressionKind::VarDeclaration {
+                name,
+                type_annotation,
+                initializer,
+                ..
+            } => {
+                self.visit(initializer);
+                // The type checker gives the variable its annotated type
+                let ty = match type_annotation {
+                    Some(annotation) => Type::from_name(&annotation.name),
+                    None => initializer.ty.clone(),
+                };
+                self.declare(name, location, ty);
+            }
+            _ => {
+                for child in children(expression) {
+                    self.visit(child);
+                }
+            }
+        }
+    }
+}
+
+// What the language server knows about one version of a document. Works
+// with whatever parses when there are errors.
+pub struct Analysis {
+    tokens: Vec<Token>,
+    module: Module,
+    diagnostics: Vec<Diagnostic>,
+    declarations: Vec<Declaration>,
+    references: HashMap<usize, Option<usize>>,
+}
+
+impl Analysis {
+    pub fn new(source: &str, filename: &str) -> Analysis {
+        let (tokens, lex_errors) = tokenize_recovering(source.to_string(), filename);
+        let (mut module, parse_errors) = parse_module_recovering(tokens.clone(), filename);
+        let mut diagnostics: Vec<Diagnostic> =
+            lex_errors.iter().map(LexError::diagnostic).collect();
+        diagnostics.extend(parse_errors.iter().map(ParseError::diagnostic));
+        // A partial module would only give misleading type errors. Types are
+        // filled in up to the first type error.
+        if diagnostics.is_empty() {
+            if let Err(error) = typecheck_module(&mut module) {
+                diagnostics.push(error.diagnostic());
+            }
+        }
+        let mut resolver = Resolver::new(&tokens);
+        resolver.resolve_module(&module);
+        let (declarations, references) = (resolver.declarations, resolver.references);
+        Analysis {
+            tokens,
+            module,
+            diagnostics,
+            declarations,
+            references,
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // The token under the cursor, or the one right before it so that the
+    // end of a name counts too
+    pub fn token_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .iter()
+            .find(|token| token.span().contains(&offset))
+            .or_else(|| self.tokens.iter().find(|token| token.span().end == offset))
+    }
+
+    pub fn definition(&self, offset: usize) -> Option<&Declaration> {
+        let token = self.token_at(offset)?;
+        let start = token.location().offset();
+        let declared_here = self
+            .declarations
+            .iter()
+            .find(|declaration| declaration.token.location().offset() == start);
+        if declared_here.is_some() {
+            return declared_here;
+        }
+        let index = (*self.references.get(&start)?)?;
+        self.declarations.get(index)
+    }
+
+    // Names show their type, other tokens the type of the expression they
+    // start
+    pub fn hover(&self, offset: usize) -> Option<(&Token, String)> {
+        let token = self.token_at(offset)?;
+        if *token.token_type() == TokenType::Identifier {
+            let ty = match self.definition(offset) {
+                Some(declaration) => declaration.ty.clone(),
+                None => builtin_types()
+                    .into_iter()
+                    .find(|(name, _)| *name == token.value())
+                    .map(|(_, ty)| ty),
+            }?;
+            return Some((token, format!("{}: {}", token.value(), ty)));
+        }
+        let ty = self.expression_at(token.location().offset())?.ty.as_ref()?;
+        Some((token, ty.to_string()))
+    }
+
+    // The innermost expression starting at the offset
+    fn expression_at(&self, offset: usize) -> Option<&Expression> {
+        let mut found = None;
+        let mut pending: Vec<&Expression> = self
+            .module
+            .functions
+            .iter()
+            .map(|function| &function.body)
+            .chain([&self.module.body])
+            .collect();
+        while let Some(expression) = pending.pop() {
+            if expression.location.offset() == offset {
+                found = Some(expression);
+            }
+            pending.extend(children(expression));
+        }
+        found
+    }
+
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        for function in &self.module.functions {
+            let declaration = self.declarations.iter().find(|declaration| {
+                declaration.name == function.name
+                    && declaration.token.location().offset() > function.location.offset()
+            });
+            let Some(declaration) = declaration else {
+                continue;
+            };
+            symbols.push(Symbol {
+                name: function.name.clone(),
+                ty: declaration.ty.clone(),
+                start: function.location.clone(),
+                end: function.body.end_location.clone(),
+                name_token: declaration.token.clone(),
+            });
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::analysis::*;
+
+    const SOURCE: &str = "fun add(a: Int, b: Int): Int {
+    var sum = a + b;
+    sum
+}
+var x = add(1, 2);
+{ var x = true; x }
+x = x + 1;
+print_int(x)
+";
+
+    fn offset(text: &str, occurrence: usize) -> usize {
+        SOURCE.match_indices(text).nth(occurrence).unwrap().0
+    }
+
+    fn definition(text: &str, occurrence: usize) -> Option<(usize, usize)> {
+        let analysis = Analysis::new(SOURCE, "a.scl");
+        let declaration = analysis.definition(offset(text, occurrence))?;
+        let location = declaration.token.location();
+        Some((location.line(), location.col()))
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(definition("a + b", 0), Some((1, 9)));
+        assert_eq!(definition("sum\n}", 0), Some((2, 9)));
+        assert_eq!(definition("add(1", 0), Some((1, 5)));
+        assert_eq!(definition("x }", 0), Some((6, 7)));
+        assert_eq!(definition("x = x", 0), Some((5, 5)));
+        assert_eq!(definition("x + 1", 0), Some((5, 5)));
+        assert_eq!(definition("x)", 0), Some((5, 5)));
+        assert_eq!(definition("print_int", 0), None);
+        // On the declaration itself
+        assert_eq!(definition("add", 0), Some((1, 5)));
+    }
+
+    #[test]
+    fn hover() {
+        let analysis = Analysis::new(SOURCE, "a.scl");
+        let hover = |text: &str, occurrence: usize| {
+            analysis
+                .hover(offset(text, occurrence))
+                .map(|(_, text)| text)
+        };
+        assert_eq!(hover("add", 0).unwrap(), "add: (Int, Int) => Int");
+        assert_eq!(hover("a + b", 0).unwrap(), "a: Int");
+        assert_eq!(hover("sum", 0).unwrap(), "sum: Int");
+        assert_eq!(hover("x }", 0).unwrap(), "x: Bool");
+        assert_eq!(hover("print_int", 0).unwrap(), "print_int: (Int) => Unit");
+        assert_eq!(hover("{ var", 0).unwrap(), "Bool");
+        assert_eq!(hover("1, 2", 0).unwrap(), "Int");
+        // Between tokens
+        assert_eq!(analysis.hover(offset("    var", 0) + 1), None);
+
+        let source = "fun f(): Int { var x: Int = return 1; x }";
+        let analysis = Analysis::new(source, "a.scl");
+        let (_, text) = analysis.hover(source.rfind('x').unwrap()).unwrap();
+        assert_eq!(text, "x: Int");
+    }
+
+    #[test]
+    fn symbols() {
+        let symbols = Analysis::new(SOURCE, "a.scl").symbols();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "add");
+        assert_eq!(
+            symbols[0].ty,
+            Some(Type::function(vec![Type::Int, Type::Int], Type::Int))
+        );
+        assert_eq!((symbols[0].start.line(), symbols[0].start.col()), (1, 1));
+        assert_eq!((symbols[0].end.line(), symbols[0].end.col()), (4, 2));
+        assert_eq!(symbols[0].name_token.value(), "add");
+    }
+
+    #[test]
+    fn diagnostics() {
+        let messages = |source: &str| -> Vec<String> {
+            Analysis::new(source, "a.scl")
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.message().to_string())
+                .collect()
+        };
+        assert_eq!(messages(SOURCE), Vec::<String>::new());
+        assert_eq!(
+            messages("var x = 1 $;\nx +;"),
+            [
+                "unexpected character '$'",
+                "expected an expression, found ';'"
+            ]
+        );
+        assert_eq!(
+            messages("var x = 1; x + true"),
+            ["operator '+' expects operands of type Int, got Int and Bool"]
+        );
+        // Resolution works without types
+        let analysis = Analysis::new("var x = 1; x; x +;", "a.scl");
+        assert_eq!(analysis.definition(11).unwrap().name, "x");
+    }
+}
@@ -0,0 +1,6 @@
+mod analysis;
+mod protocol;
+mod server;
+
+pub use analysis::{Analysis, Declaration, Symbol};
+pub use server::run;
@@ -0,0 +1,111 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+// Reads the body of the next message, None at the end of input. Messages
+// are framed by a `Content-Length` header followed by an empty line.
+pub fn read_message(input: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+pub fn write_message(out: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+// LSP positions count lines from 0 and characters in UTF-16 code units
+pub fn position(source: &str, offset: usize) -> Value {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+pub fn range(source: &str, start: usize, end: usize) -> Value {
+    json!({
+        "start": position(source, start),
+        "end": position(source, end),
+    })
+}
+
+// The byte offset of an LSP position. Positions past the end of a line
+// mean the end of that line.
+pub fn offset(source: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let mut line_start = 0;
+    for _ in 0..line {
+        line_start += source[line_start..].find('\n')? + 1;
+    }
+    let line_text = source[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in line_text.char_indices() {
+        if units >= character {
+            return Some(line_start + index);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line_text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::protocol::*;
+
+    #[test]
+    fn framing() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({"id": 1})).unwrap();
+        assert_eq!(out, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+        let mut input: &[u8] =
+            b"Content-Length: 2\r\nContent-Type: x\r\n\r\n{}content-length: 1\r\n\r\n1";
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), b"{}");
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), b"1");
+        assert!(read_message(&mut input).unwrap().is_none());
+        let mut input: &[u8] = b"\r\n{}";
+        assert!(read_message(&mut input).is_err());
+    }
+
+    #[test]
+    fn positions() {
+        let source = "ab\nä𝄞x\n";
+        assert_eq!(position(source, 0), json!({"line": 0, "character": 0}));
+        assert_eq!(position(source, 3), json!({"line": 1, "character": 0}));
+        // ä is one UTF-16 unit and 𝄞 two
+        assert_eq!(position(source, 9), json!({"line": 1, "character": 3}));
+        assert_eq!(position(source, 100), json!({"line": 2, "character": 0}));
+        assert_eq!(offset(source, &json!({"line": 1, "character": 3})), Some(9));
+        assert_eq!(
+            offset(source, &json!({"line": 1, "character": 50})),
+            Some(10)
+        );
+        assert_eq!(offset(source, &json!({"line": 0, "character": 1})), Some(1));
+        assert_eq!(offset(source, &json!({"line": 5, "character": 0})), None);
+        assert_eq!(offset(source, &json!({"line": 1})), None);
+    }
+}
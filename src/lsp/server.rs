@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::diagnostics::{Diagnostic, Span};
+
+use super::analysis::Analysis;
+use super::protocol::{offset, range, read_message, write_message};
+
+// Error codes defined by JSON-RPC
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+const SEVERITY_ERROR: i64 = 1;
+const SYMBOL_KIND_FUNCTION: i64 = 12;
+// Clients send the whole document on every change
+const TEXT_DOCUMENT_SYNC_FULL: i64 = 1;
+
+fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: &Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn span_range(source: &str, span: &Span) -> Value {
+    let start = span.location().offset();
+    range(source, start, start + span.length())
+}
+
+fn lsp_diagnostic(source: &str, uri: &str, diagnostic: &Diagnostic) -> Value {
+    let range = match diagnostic.span() {
+        Some(span) => span_range(source, span),
+        None => range(source, 0, 0),
+    };
+    let mut message = diagnostic.message().to_string();
+    for note in diagnostic.notes() {
+        message += &format!("\nnote: {}", note);
+    }
+    let related: Vec<Value> = diagnostic
+        .labels()
+        .iter()
+        .map(|label| {
+            json!({
+                "location": { "uri": uri, "range": span_range(source, label.span()) },
+                "message": label.message(),
+            })
+        })
+        .collect();
+    json!({
+        "range": range,
+        "severity": SEVERITY_ERROR,
+        "source": "scl",
+        "message": message,
+        "relatedInformation": related,
+    })
+}
+
+// Open documents by URI. Everything is recomputed from the text on each
+// request, which is quick enough for programs of this size.
+struct Server {
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server {
+            documents: HashMap::new(),
+        }
+    }
+
+    // Returns the messages to send back
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let id = match message.get("id") {
+            Some(id) => id,
+            None => return self.handle_notification(method, params, uri),
+        };
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "scl" },
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/hover" => self.hover(uri, &params["position"]),
+            "textDocument/definition" => self.definition(uri, &params["position"]),
+            "textDocument/documentSymbol" => self.symbols(uri),
+            _ => {
+                return vec![error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("unknown method '{}'", method),
+                )]
+            }
+        };
+        vec![response(id, result)]
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value, uri: &str) -> Vec<Value> {
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                let params = json!({ "uri": uri, "diagnostics": [] });
+                return vec![notification("textDocument/publishDiagnostics", params)];
+            }
+            _ => None,
+        };
+        match text {
+            Some(text) => {
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![self.publish_diagnostics(uri)]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn analyze(&self, uri: &str) -> Option<(&str, Analysis)> {
+        let source = self.documents.get(uri)?;
+        Some((source, Analysis::new(source, uri)))
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics: Vec<Value> = match self.analyze(uri) {
+            Some((source, analysis)) => analysis
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| lsp_diagnostic(source, uri, diagnostic))
+                .collect(),
+            None => Vec::new(),
+        };
+        let params = json!({ "uri": uri, "diagnostics": diagnostics });
+        notification("textDocument/publishDiagnostics", params)
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let hover = || {
+            let (source, analysis) = self.analyze(uri)?;
+            let (token, text) = analysis.hover(offset(source, position)?)?;
+            Some(json!({
+                "contents": { "kind": "markdown", "value": format!("```scl\n{}\n```", text) },
+                "range": range(source, token.span().start, token.span().end),
+            }))
+        };
+        hover().unwrap_or(Value::Null)
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let definition = || {
+            let (source, analysis) = self.analyze(uri)?;
+            let declaration = analysis.definition(offset(source, position)?)?;
+            let span = declaration.token.span();
+            Some(json!({ "uri": uri, "range": range(source, span.start, span.end) }))
+        };
+        definition().unwrap_or(Value::Null)
+    }
+
+    fn symbols(&self, uri: &str) -> Value {
+        let Some((source, analysis)) = self.analyze(uri) else {
+            return Value::Null;
+        };
+        let symbols: Vec<Value> = analysis
+            .symbols()
+            .iter()
+            .map(|symbol| {
+                let name_span = symbol.name_token.span();
+                json!({
+                    "name": symbol.name,
+                    "detail": symbol.ty.as_ref().map(|ty| ty.to_string()),
+                    "kind": SYMBOL_KIND_FUNCTION,
+                    "range": range(source, symbol.start.offset(), symbol.end.offset()),
+                    "selectionRange": range(source, name_span.start, name_span.end),
+                })
+            })
+            .collect();
+        Value::Array(symbols)
+    }
+}
+
+// Serves one client until it sends `exit` or closes the input
+pub fn run(input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(input)? {
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(error) => {
+                let response = error_response(&Value::Null, PARSE_ERROR, error.to_string());
+                write_message(out, &response)?;
+                continue;
+            }
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        for response in server.handle(&message) {
+            write_message(out, &response)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::server::*;
+
+    const URI: &str = "file:///a.scl";
+
+    fn request(id: i64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn position_params(line: usize, character: usize) -> Value {
+        json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        })
+    }
+
+    fn only(mut messages: Vec<Value>) -> Value {
+        assert_eq!(messages.len(), 1);
+        messages.remove(0)
+    }
+
+    #[test]
+    fn session() {
+        let mut server = Server::new();
+        let initialize = only(server.handle(&request(1, "initialize", json!({}))));
+        assert_eq!(initialize["result"]["capabilities"]["textDocumentSync"], 1);
+        assert!(server
+            .handle(&notification("initialized", json!({})))
+            .is_empty());
+
+        let open = notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "text": "var x = 1;\nx + true" } }),
+        );
+        let published = only(server.handle(&open));
+        assert_eq!(published["method"], "textDocument/publishDiagnostics");
+        assert_eq!(
+            published["params"]["diagnostics"],
+            json!([{
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 1, "character": 8 },
+                },
+                "severity": 1,
+                "source": "scl",
+                "message": "operator '+' expects operands of type Int, got Int and Bool",
+                "relatedInformation": [],
+            }])
+        );
+
+        let change = notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI },
+                "contentChanges": [{ "text": "fun f(a: Int): Int {\n  a\n}\nvar x = f(1);\nx" }],
+            }),
+        );
+        let published = only(server.handle(&change));
+        assert_eq!(published["params"]["diagnostics"], json!([]));
+
+        let hover = only(server.handle(&request(2, "textDocument/hover", position_params(4, 0))));
+        assert_eq!(
+            hover["result"],
+            json!({
+                "contents": { "kind": "markdown", "value": "```scl\nx: Int\n```" },
+                "range": {
+                    "start": { "line": 4, "character": 0 },
+                    "end": { "line": 4, "character": 1 },
+                },
+            })
+        );
+        let hover = only(server.handle(&request(3, "textDocument/hover", position_params(2, 5))));
+        assert_eq!(hover["result"], Value::Null);
+
+        let definition = only(server.handle(&request(
+            4,
+            "textDocument/definition",
+            position_params(3, 8),
+        )));
+        assert_eq!(
+            definition["result"],
+            json!({
+                "uri": URI,
+                "range": {
+                    "start": { "line": 0, "character": 4 },
+                    "end": { "line": 0, "character": 5 },
+                },
+            })
+        );
+
+        let symbols = only(server.handle(&request(
+            5,
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        )));
+        assert_eq!(
+            symbols["result"],
+            json!([{
+                "name": "f",
+                "detail": "(Int) => Int",
+                "kind": 12,
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 2, "character": 1 },
+                },
+                "selectionRange": {
+                    "start": { "line": 0, "character": 4 },
+                    "end": { "line": 0, "character": 5 },
+                },
+            }])
+        );
+
+        let unknown = only(server.handle(&request(6, "workspace/symbol", json!({}))));
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+
+        let close = notification(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": URI } }),
+        );
+        assert_eq!(
+            only(server.handle(&close))["params"]["diagnostics"],
+            json!([])
+        );
+        let shutdown = only(server.handle(&request(7, "shutdown", Value::Null)));
+        assert_eq!(shutdown["result"], Value::Null);
+    }
+
+    #[test]
+    fn over_stdio() {
+        let mut input = Vec::new();
+        for message in [
+            request(1, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+            request(2, "shutdown", Value::Null),
+        ] {
+            write_message(&mut input, &message).unwrap();
+        }
+        input.extend_from_slice(b"Content-Length: 1\r\n\r\n{");
+        let mut out = Vec::new();
+        run(&mut input.as_slice(), &mut out).unwrap();
+        let mut out = out.as_slice();
+        let response: Value =
+            serde_json::from_slice(&read_message(&mut out).unwrap().unwrap()).unwrap();
+        assert_eq!(
+            response,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": null })
+        );
+        // Nothing is answered after exit
+        assert!(read_message(&mut out).unwrap().is_none());
+
+        let mut input: &[u8] = b"Content-Length: 1\r\n\r\n{";
+        let mut out = Vec::new();
+        run(&mut input, &mut out).unwrap();
+        let response: Value =
+            serde_json::from_slice(&read_message(&mut out.as_slice()).unwrap().unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+    }
+}
@@ -1,12 +1,17 @@
-mod tokenizer;
-use regex::Regex;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::process::ExitCode;
 
-use crate::tokenizer::WHITESPACE_REGEX_STR;
+use scl::cli;
 
-fn main() {
-    println!("Hello, world!");
-    let source_code = "   ";
-    let whitespeace_regex = Regex::new(WHITESPACE_REGEX_STR).unwrap();
-    let is_whitespace = whitespeace_regex.is_match(source_code);
-    println!("{}", is_whitespace);
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    // See https://no-color.org
+    let colour = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    ExitCode::from(cli::run_and_report(
+        &arguments,
+        &mut io::stdout(),
+        &mut io::stderr(),
+        colour,
+    ))
 }
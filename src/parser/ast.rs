@@ -0,0 +1,332 @@
+use std::fmt;
+
+use crate::diagnostics::Span;
+use crate::tokenizer::Location;
+use crate::typechecker::Type;
+
+// `And` and `Or` short-circuit: the right operand is evaluated only when the
+// left one does not already decide the result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+impl UnaryOperator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "not",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+// Type written in the source, for example the `Int` in `var x: Int = 1`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeAnnotation {
+    pub name: String,
+    pub location: Location,
+}
+
+impl TypeAnnotation {
+    pub fn span(&self) -> Span {
+        Span::new(self.location.clone(), self.name.len())
+    }
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpressionKind {
+    IntLiteral(i64),
+    BoolLiteral(bool),
+    UnitLiteral,
+    Identifier(String),
+    BinaryOp {
+        left: Box<Expression>,
+        operator: BinaryOperator,
+        right: Box<Expression>,
+    },
+    UnaryOp {
+        operator: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    Assignment {
+        name: String,
+        value: Box<Expression>,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+    },
+    // `result` is None when the last expression is followed by a semicolon
+    Block {
+        expressions: Vec<Expression>,
+        result: Option<Box<Expression>>,
+    },
+    If {
+        condition: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Option<Box<Expression>>,
+    },
+    While {
+        condition: Box<Expression>,
+        body: Box<Expression>,
+    },
+    // Only allowed directly inside a block or at the top level
+    VarDeclaration {
+        name: String,
+        constant: bool,
+        type_annotation: Option<TypeAnnotation>,
+        initializer: Box<Expression>,
+    },
+    // `value` is None for a bare `return`, which returns unit
+    Return {
+        value: Option<Box<Expression>>,
+    },
+    // Only allowed inside a loop. The value becomes the value of the loop.
+    Break {
+        value: Option<Box<Expression>>,
+    },
+    Continue,
+}
+
+// Every node carries the location of its first token and the location just
+// past its last token. `ty` is filled in by the type checker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub location: Location,
+    pub end_location: Location,
+    pub ty: Option<Type>,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, location: Location, end_location: Location) -> Expression {
+        Expression {
+            kind,
+            location,
+            end_location,
+            ty: None,
+        }
+    }
+
+    // The source of the expression, for diagnostics
+    pub fn span(&self) -> Span {
+        let length = self.end_location.offset() - self.location.offset();
+        Span::new(self.location.clone(), length)
+    }
+}
+
+// Fully parenthesised form, mainly useful for inspecting the shape of the tree
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ExpressionKind::IntLiteral(value) => write!(f, "{}", value),
+            ExpressionKind::BoolLiteral(value) => write!(f, "{}", value),
+            ExpressionKind::UnitLiteral => write!(f, "unit"),
+            ExpressionKind::Identifier(name) => write!(f, "{}", name),
+            ExpressionKind::BinaryOp {
+                left,
+                operator,
+                right,
+            } => write!(f, "({} {} {})", left, operator, right),
+            ExpressionKind::UnaryOp { operator, operand } => match operator {
+                UnaryOperator::Negate => write!(f, "({}{})", operator, operand),
+                UnaryOperator::Not => write!(f, "({} {})", operator, operand),
+            },
+            ExpressionKind::Assignment { name, value } => write!(f, "({} = {})", name, value),
+            ExpressionKind::Call { name, arguments } => {
+                write!(f, "{}(", name)?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            }
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => {
+                write!(f, "{{")?;
+                for (index, expression) in expressions.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{};", expression)?;
+                }
+                if let Some(result) = result {
+                    if !expressions.is_empty() {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", result)?;
+                }
+                write!(f, "}}")
+            }
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match else_branch {
+                Some(else_branch) => write!(
+                    f,
+                    "(if {} then {} else {})",
+                    condition, then_branch, else_branch
+                ),
+                None => write!(f, "(if {} then {})", condition, then_branch),
+            },
+            ExpressionKind::While { condition, body } => {
+                write!(f, "(while {} do {})", condition, body)
+            }
+            ExpressionKind::VarDeclaration {
+                name,
+                constant,
+                type_annotation,
+                initializer,
+            } => {
+                let keyword = if *constant { "const" } else { "var" };
+                write!(f, "({} {}", keyword, name)?;
+                if let Some(type_annotation) = type_annotation {
+                    write!(f, ": {}", type_annotation)?;
+                }
+                write!(f, " = {})", initializer)
+            }
+            ExpressionKind::Return { value } => match value {
+                Some(value) => write!(f, "(return {})", value),
+                None => write!(f, "(return)"),
+            },
+            ExpressionKind::Break { value } => match value {
+                Some(value) => write!(f, "(break {})", value),
+                None => write!(f, "(break)"),
+            },
+            ExpressionKind::Continue => write!(f, "(continue)"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub type_annotation: TypeAnnotation,
+    pub location: Location,
+}
+
+impl Parameter {
+    // The name of the parameter
+    pub fn span(&self) -> Span {
+        Span::new(self.location.clone(), self.name.len())
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.type_annotation)
+    }
+}
+
+// `fun name(a: Int): Int { ... }`, a missing return type means Unit
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<TypeAnnotation>,
+    pub body: Expression,
+    pub location: Location,
+}
+
+impl FunctionDefinition {
+    // From `fun` to the end of the body
+    pub fn span(&self) -> Span {
+        let length = self.body.end_location.offset() - self.location.offset();
+        Span::new(self.location.clone(), length)
+    }
+}
+
+impl fmt::Display for FunctionDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fun {}(", self.name)?;
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, ")")?;
+        if let Some(return_type) = &self.return_type {
+            write!(f, ": {}", return_type)?;
+        }
+        write!(f, " {}", self.body)
+    }
+}
+
+// A whole program: the function definitions and the top-level expressions
+// around them, which make up the body of main
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub functions: Vec<FunctionDefinition>,
+    pub body: Expression,
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for function in &self.functions {
+            writeln!(f, "{}", function)?;
+        }
+        write!(f, "{}", self.body)
+    }
+}
@@ -0,0 +1,4 @@
+mod ast;
+mod parse;
+pub use ast::*;
+pub use parse::{parse, parse_input, parse_module, parse_module_recovering, ParseError};
@@ -0,0 +1,1142 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::tokenizer::{
+    Keyword, Location, Operator, Punctuation, Token, TokenType, UNNAMED_SOURCE,
+};
+
+use super::ast::*;
+
+// Left associative binary operators from the loosest to the tightest binding level.
+// Assignment is handled separately since it is right associative.
+const LEFT_ASSOCIATIVE_OPERATORS: [&[(TokenType, BinaryOperator)]; 6] = [
+    &[(TokenType::Keyword(Keyword::Or), BinaryOperator::Or)],
+    &[(TokenType::Keyword(Keyword::And), BinaryOperator::And)],
+    &[
+        (TokenType::Operator(Operator::Eq), BinaryOperator::Equal),
+        (TokenType::Operator(Operator::Neq), BinaryOperator::NotEqual),
+    ],
+    &[
+        (TokenType::Operator(Operator::Less), BinaryOperator::Less),
+        (
+            TokenType::Operator(Operator::Leq),
+            BinaryOperator::LessOrEqual,
+        ),
+        (
+            TokenType::Operator(Operator::Greater),
+            BinaryOperator::Greater,
+        ),
+        (
+            TokenType::Operator(Operator::Geq),
+            BinaryOperator::GreaterOrEqual,
+        ),
+    ],
+    &[
+        (TokenType::Operator(Operator::Plus), BinaryOperator::Add),
+        (
+            TokenType::Operator(Operator::Minus),
+            BinaryOperator::Subtract,
+        ),
+    ],
+    &[
+        (
+            TokenType::Operator(Operator::Asterisk),
+            BinaryOperator::Multiply,
+        ),
+        (TokenType::Operator(Operator::Slash), BinaryOperator::Divide),
+        (
+            TokenType::Operator(Operator::Percent),
+            BinaryOperator::Remainder,
+        ),
+    ],
+];
+
+// Prefix operators bind tighter than any binary operator
+const UNARY_OPERATORS: [(TokenType, UnaryOperator); 2] = [
+    (TokenType::Operator(Operator::Minus), UnaryOperator::Negate),
+    (TokenType::Keyword(Keyword::Not), UnaryOperator::Not),
+];
+
+const ASSIGN: TokenType = TokenType::Operator(Operator::Assign);
+const BRACKET_CLOSE: TokenType = TokenType::Punctuation(Punctuation::BracketClose);
+const BRACKET_OPEN: TokenType = TokenType::Punctuation(Punctuation::BracketOpen);
+const COLON: TokenType = TokenType::Punctuation(Punctuation::Colon);
+const COMMA: TokenType = TokenType::Punctuation(Punctuation::Comma);
+const CURLY_CLOSE: TokenType = TokenType::Punctuation(Punctuation::CurlyClose);
+const CURLY_OPEN: TokenType = TokenType::Punctuation(Punctuation::CurlyOpen);
+const SEMICOLON: TokenType = TokenType::Punctuation(Punctuation::Semicolon);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    location: Location,
+    // Length of the offending token, 0 at the end of input
+    length: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(location: Location, message: String) -> ParseError {
+        ParseError {
+            location,
+            length: 0,
+            message,
+        }
+    }
+
+    fn at_token(token: &Token, message: String) -> ParseError {
+        ParseError {
+            location: token.location().clone(),
+            length: token.span().len(),
+            message,
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::at(
+            self.message.clone(),
+            Span::new(self.location.clone(), self.length),
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+enum SequenceItem {
+    Function(FunctionDefinition),
+    // An expression followed by a semicolon
+    Statement(Expression),
+    // An expression that may be the result of the sequence
+    Result(Expression),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // Location used for errors once all tokens are consumed: just past the
+    // last token, or the start of the file when there are none
+    end_location: Location,
+    // Errors recovered from so far, in the order they were found
+    errors: Vec<ParseError>,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>, filename: &str) -> Parser {
+        let end_location = match tokens.last() {
+            Some(token) => token.end_location().clone(),
+            None => Location::start(filename),
+        };
+        Parser {
+            tokens,
+            position: 0,
+            end_location,
+            errors: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_type(&self) -> Option<&TokenType> {
+        self.peek().map(|token| token.token_type())
+    }
+
+    fn peek_is(&self, token_type: &TokenType) -> bool {
+        self.peek_type() == Some(token_type)
+    }
+
+    fn peek_is_keyword(&self, keyword: Keyword) -> bool {
+        self.peek_is(&TokenType::Keyword(keyword))
+    }
+
+    // Location just past the last consumed token
+    fn previous_end(&self) -> Location {
+        match self.position.checked_sub(1) {
+            Some(index) => self.tokens[index].end_location().clone(),
+            None => self.end_location.clone(),
+        }
+    }
+
+    // A node that starts at `location` and ends with the last consumed token
+    fn ending_here(&self, kind: ExpressionKind, location: Location) -> Expression {
+        Expression::new(kind, location, self.previous_end())
+    }
+
+    fn current_location(&self) -> Location {
+        match self.peek() {
+            Some(token) => token.location().clone(),
+            None => self.end_location.clone(),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::at_token(
+                token,
+                format!("expected {}, found '{}'", expected, token.value()),
+            ),
+            None => ParseError::new(
+                self.end_location.clone(),
+                format!("expected {}, found end of input", expected),
+            ),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn consume(&mut self, token_type: &TokenType) -> Result<Token, ParseError> {
+        if self.peek_is(token_type) {
+            self.next(&token_type.to_string())
+        } else {
+            Err(self.unexpected(&token_type.to_string()))
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: Keyword) -> Result<Token, ParseError> {
+        self.consume(&TokenType::Keyword(keyword))
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.parse_assignment()
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expression, ParseError> {
+        let left = self.parse_binary(0)?;
+        if !self.peek_is(&ASSIGN) {
+            return Ok(left);
+        }
+        let assign = self.consume(&ASSIGN)?;
+        let value = self.parse_assignment()?;
+        match left.kind {
+            ExpressionKind::Identifier(name) => Ok(self.ending_here(
+                ExpressionKind::Assignment {
+                    name,
+                    value: Box::new(value),
+                },
+                left.location,
+            )),
+            _ => Err(ParseError::at_token(
+                &assign,
+                "left side of an assignment must be a variable name".to_string(),
+            )),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expression, ParseError> {
+        if level == LEFT_ASSOCIATIVE_OPERATORS.len() {
+            return self.parse_unary();
+        }
+        let mut left = self.parse_binary(level + 1)?;
+        while let Some(operator) = self.peek_binary_operator(level) {
+            self.next("an operator")?;
+            let right = self.parse_binary(level + 1)?;
+            let location = left.location.clone();
+            left = self.ending_here(
+                ExpressionKind::BinaryOp {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                },
+                location,
+            );
+        }
+        Ok(left)
+    }
+
+    fn peek_binary_operator(&self, level: usize) -> Option<BinaryOperator> {
+        let token_type = self.peek_type()?;
+        LEFT_ASSOCIATIVE_OPERATORS[level]
+            .iter()
+            .find(|(candidate, _)| candidate == token_type)
+            .map(|(_, binary_operator)| *binary_operator)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        let operator = match self.peek_type() {
+            Some(token_type) => UNARY_OPERATORS
+                .iter()
+                .find(|(candidate, _)| candidate == token_type)
+                .map(|(_, unary_operator)| *unary_operator),
+            None => None,
+        };
+        let operator = match operator {
+            Some(operator) => operator,
+            None => return self.parse_factor(),
+        };
+        let location = self.next("an operator")?.location().clone();
+        let operand = self.parse_unary()?;
+        Ok(self.ending_here(
+            ExpressionKind::UnaryOp {
+                operator,
+                operand: Box::new(operand),
+            },
+            location,
+        ))
+    }
+
+    fn parse_factor(&mut self) -> Result<Expression, ParseError> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected("an expression")),
+        };
+        match token.token_type() {
+            TokenType::Punctuation(Punctuation::BracketOpen) => self.parse_parenthesized(),
+            TokenType::Punctuation(Punctuation::CurlyOpen) => self.parse_block(),
+            TokenType::Keyword(Keyword::If) => self.parse_if(),
+            TokenType::Keyword(Keyword::Return) => self.parse_return(),
+            TokenType::Keyword(Keyword::Break) => self.parse_break(),
+            TokenType::Keyword(Keyword::Continue) => {
+                self.next("'continue'")?;
+                Ok(self.ending_here(
+                    ExpressionKind::Continue,
+                    token.location().clone(),
+                ))
+            }
+            TokenType::Keyword(Keyword::Fun) => Err(ParseError::at_token(
+                &token,
+                "function definitions are only allowed at the top level".to_string(),
+            )),
+            TokenType::Keyword(Keyword::While) => self.parse_while(),
+            TokenType::Keyword(Keyword::Var) | TokenType::Keyword(Keyword::Const) => {
+                Err(ParseError::at_token(
+                    &token,
+                    format!(
+                        "'{}' declarations are only allowed directly inside a block or at the top level",
+                        token.value()
+                    ),
+                ))
+            }
+            TokenType::UnitLiteral => {
+                self.next("unit")?;
+                Ok(self.ending_here(
+                    ExpressionKind::UnitLiteral,
+                    token.location().clone(),
+                ))
+            }
+            TokenType::IntLiteral => self.parse_int_literal(),
+            TokenType::BoolLiteral => {
+                self.next("a boolean")?;
+                Ok(self.ending_here(
+                    ExpressionKind::BoolLiteral(token.value() == "true"),
+                    token.location().clone(),
+                ))
+            }
+            TokenType::Identifier => self.parse_identifier_or_call(),
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    fn parse_parenthesized(&mut self) -> Result<Expression, ParseError> {
+        self.consume(&BRACKET_OPEN)?;
+        let expression = self.parse_expression()?;
+        self.consume(&BRACKET_CLOSE)?;
+        Ok(expression)
+    }
+
+    fn parse_block(&mut self) -> Result<Expression, ParseError> {
+        let location = self.consume(&CURLY_OPEN)?.location().clone();
+        let kind = self.parse_sequence(Some(&CURLY_CLOSE), None);
+        self.consume(&CURLY_CLOSE)?;
+        Ok(self.ending_here(kind, location))
+    }
+
+    // Parses `e1; e2; ...; result` until `end` is the next token, or until the
+    // input runs out when `end` is None. The semicolon can be left out after
+    // an expression that ends with a closing curly bracket. Function
+    // definitions are collected into `functions` when it is given.
+    //
+    // Errors in the items are recorded and parsing continues after the next
+    // `;` or `}`, so the result is only meaningful when there were none.
+    fn parse_sequence(
+        &mut self,
+        end: Option<&TokenType>,
+        mut functions: Option<&mut Vec<FunctionDefinition>>,
+    ) -> ExpressionKind {
+        let mut expressions: Vec<Expression> = Vec::new();
+        let mut result: Option<Box<Expression>> = None;
+        while self.peek().is_some() && self.peek_type() != end {
+            if let Some(previous) = result.take() {
+                expressions.push(*previous);
+            }
+            match self.parse_sequence_item(end, functions.is_some()) {
+                Ok(SequenceItem::Function(function)) => {
+                    if let Some(functions) = functions.as_mut() {
+                        functions.push(function);
+                    }
+                }
+                Ok(SequenceItem::Statement(expression)) => expressions.push(expression),
+                Ok(SequenceItem::Result(expression)) => result = Some(Box::new(expression)),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(end);
+                }
+            }
+        }
+        ExpressionKind::Block {
+            expressions,
+            result,
+        }
+    }
+
+    fn parse_sequence_item(
+        &mut self,
+        end: Option<&TokenType>,
+        allow_functions: bool,
+    ) -> Result<SequenceItem, ParseError> {
+        if allow_functions && self.peek_is_keyword(Keyword::Fun) {
+            return Ok(SequenceItem::Function(self.parse_function_definition()?));
+        }
+        let expression =
+            if self.peek_is_keyword(Keyword::Var) || self.peek_is_keyword(Keyword::Const) {
+                self.parse_var_declaration()?
+            } else {
+                self.parse_expression()?
+            };
+        if self.peek_is(&SEMICOLON) {
+            self.consume(&SEMICOLON)?;
+            Ok(SequenceItem::Statement(expression))
+        } else if self.peek().is_none() || self.peek_type() == end || self.ends_in_block() {
+            Ok(SequenceItem::Result(expression))
+        } else {
+            Err(self.unexpected("';'"))
+        }
+    }
+
+    // Skips past the rest of a broken item: up to and including the next `;`
+    // or a block started within the item, or up to the `}` ending the
+    // sequence. A stray `}` at the top level is skipped like any other token.
+    fn synchronize(&mut self, end: Option<&TokenType>) {
+        let mut depth = 0;
+        while let Some(token_type) = self.peek_type() {
+            if *token_type == CURLY_CLOSE && depth == 0 && end == Some(&CURLY_CLOSE) {
+                return;
+            }
+            let done = match token_type {
+                TokenType::Punctuation(Punctuation::CurlyOpen) => {
+                    depth += 1;
+                    false
+                }
+                TokenType::Punctuation(Punctuation::CurlyClose) if depth > 0 => {
+                    depth -= 1;
+                    depth == 0
+                }
+                TokenType::Punctuation(Punctuation::Semicolon) => depth == 0,
+                _ => false,
+            };
+            self.position += 1;
+            if done {
+                return;
+            }
+        }
+    }
+
+    fn ends_in_block(&self) -> bool {
+        self.position > 0 && *self.tokens[self.position - 1].token_type() == CURLY_CLOSE
+    }
+
+    fn parse_var_declaration(&mut self) -> Result<Expression, ParseError> {
+        let keyword = self.next("'var' or 'const'")?;
+        let name = self.parse_name()?;
+        let type_annotation = if self.peek_is(&COLON) {
+            self.consume(&COLON)?;
+            Some(self.parse_type_annotation()?)
+        } else {
+            None
+        };
+        self.consume(&ASSIGN)?;
+        let initializer = self.parse_expression()?;
+        Ok(self.ending_here(
+            ExpressionKind::VarDeclaration {
+                name,
+                constant: *keyword.token_type() == TokenType::Keyword(Keyword::Const),
+                type_annotation,
+                initializer: Box::new(initializer),
+            },
+            keyword.location().clone(),
+        ))
+    }
+
+    fn parse_name(&mut self) -> Result<String, ParseError> {
+        Ok(self.consume(&TokenType::Identifier)?.value().to_string())
+    }
+
+    fn parse_type_annotation(&mut self) -> Result<TypeAnnotation, ParseError> {
+        let location = self.current_location();
+        let name = self.parse_name()?;
+        Ok(TypeAnnotation { name, location })
+    }
+
+    fn parse_if(&mut self) -> Result<Expression, ParseError> {
+        let location = self.consume_keyword(Keyword::If)?.location().clone();
+        let condition = self.parse_expression()?;
+        self.consume_keyword(Keyword::Then)?;
+        let then_branch = self.parse_expression()?;
+        let else_branch = if self.peek_is_keyword(Keyword::Else) {
+            self.consume_keyword(Keyword::Else)?;
+            Some(Box::new(self.parse_expression()?))
+        } else {
+            None
+        };
+        Ok(self.ending_here(
+            ExpressionKind::If {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch,
+            },
+            location,
+        ))
+    }
+
+    fn parse_while(&mut self) -> Result<Expression, ParseError> {
+        let location = self.consume_keyword(Keyword::While)?.location().clone();
+        let condition = self.parse_expression()?;
+        self.consume_keyword(Keyword::Do)?;
+        let body = self.parse_expression()?;
+        Ok(self.ending_here(
+            ExpressionKind::While {
+                condition: Box::new(condition),
+                body: Box::new(body),
+            },
+            location,
+        ))
+    }
+
+    fn parse_return(&mut self) -> Result<Expression, ParseError> {
+        let location = self.consume_keyword(Keyword::Return)?.location().clone();
+        let value = self.parse_optional_value()?;
+        Ok(self.ending_here(ExpressionKind::Return { value }, location))
+    }
+
+    fn parse_break(&mut self) -> Result<Expression, ParseError> {
+        let location = self.consume_keyword(Keyword::Break)?.location().clone();
+        let value = self.parse_optional_value()?;
+        Ok(self.ending_here(ExpressionKind::Break { value }, location))
+    }
+
+    // The value after `return` or `break`, which is left out when the next
+    // token cannot start an expression
+    fn parse_optional_value(&mut self) -> Result<Option<Box<Expression>>, ParseError> {
+        let omitted = match self.peek_type() {
+            None => true,
+            Some(token_type) => {
+                [SEMICOLON, CURLY_CLOSE, BRACKET_CLOSE, COMMA].contains(token_type)
+                    || [Keyword::Else, Keyword::Then, Keyword::Do]
+                        .iter()
+                        .any(|keyword| self.peek_is_keyword(*keyword))
+            }
+        };
+        if omitted {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(self.parse_expression()?)))
+    }
+
+    fn parse_function_definition(&mut self) -> Result<FunctionDefinition, ParseError> {
+        let location = self.consume_keyword(Keyword::Fun)?.location().clone();
+        let name = self.parse_name()?;
+        self.consume(&BRACKET_OPEN)?;
+        let mut parameters: Vec<Parameter> = Vec::new();
+        if !self.peek_is(&BRACKET_CLOSE) {
+            loop {
+                let location = self.current_location();
+                let name = self.parse_name()?;
+                self.consume(&COLON)?;
+                let type_annotation = self.parse_type_annotation()?;
+                parameters.push(Parameter {
+                    name,
+                    type_annotation,
+                    location,
+                });
+                if self.peek_is(&COMMA) {
+                    self.consume(&COMMA)?;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.consume(&BRACKET_CLOSE)?;
+        let return_type = if self.peek_is(&COLON) {
+            self.consume(&COLON)?;
+            Some(self.parse_type_annotation()?)
+        } else {
+            None
+        };
+        if !self.peek_is(&CURLY_OPEN) {
+            return Err(self.unexpected("'{'"));
+        }
+        let body = self.parse_block()?;
+        Ok(FunctionDefinition {
+            name,
+            parameters,
+            return_type,
+            body,
+            location,
+        })
+    }
+
+    fn parse_int_literal(&mut self) -> Result<Expression, ParseError> {
+        let token = self.next("an integer")?;
+        match token.value().parse::<i64>() {
+            Ok(value) => {
+                Ok(self.ending_here(ExpressionKind::IntLiteral(value), token.location().clone()))
+            }
+            Err(_) => Err(ParseError::at_token(
+                &token,
+                format!("integer literal '{}' is too large", token.value()),
+            )),
+        }
+    }
+
+    fn parse_identifier_or_call(&mut self) -> Result<Expression, ParseError> {
+        let token = self.consume(&TokenType::Identifier)?;
+        let name = token.value().to_string();
+        if !self.peek_is(&BRACKET_OPEN) {
+            return Ok(self.ending_here(ExpressionKind::Identifier(name), token.location().clone()));
+        }
+        self.consume(&BRACKET_OPEN)?;
+        let mut arguments: Vec<Expression> = Vec::new();
+        if !self.peek_is(&BRACKET_CLOSE) {
+            loop {
+                arguments.push(self.parse_expression()?);
+                if self.peek_is(&COMMA) {
+                    self.consume(&COMMA)?;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.consume(&BRACKET_CLOSE)?;
+        Ok(self.ending_here(
+            ExpressionKind::Call { name, arguments },
+            token.location().clone(),
+        ))
+    }
+}
+
+// A single expression without a trailing semicolon is returned as is
+fn top_level_expression(
+    kind: ExpressionKind,
+    location: Location,
+    end_location: Location,
+) -> Expression {
+    match kind {
+        ExpressionKind::Block {
+            expressions,
+            result: Some(result),
+        } if expressions.is_empty() => *result,
+        kind => Expression::new(kind, location, end_location),
+    }
+}
+
+// The top level of a program is a sequence of expressions like the inside of
+// a block. The tokens are expected to come from `tokenize`, so an empty input
+// is reported in the unnamed source.
+pub fn parse(tokens: Vec<Token>) -> Result<Expression, ParseError> {
+    let mut parser = Parser::new(tokens, UNNAMED_SOURCE);
+    if parser.peek().is_none() {
+        return Err(parser.unexpected("an expression"));
+    }
+    let location = parser.current_location();
+    let kind = parser.parse_sequence(None, None);
+    let end_location = parser.previous_end();
+    match parser.errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(top_level_expression(kind, location, end_location)),
+    }
+}
+
+// Like `parse`, but also allows function definitions among the top-level
+// expressions. `filename` names the source in errors when there are no tokens.
+pub fn parse_module(tokens: Vec<Token>, filename: &str) -> Result<Module, ParseError> {
+    let (module, errors) = parse_module_recovering(tokens, filename);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(module),
+    }
+}
+
+// Like `parse_module`, but returns every syntax error instead of the first.
+// The module is partial when there are errors.
+pub fn parse_module_recovering(tokens: Vec<Token>, filename: &str) -> (Module, Vec<ParseError>) {
+    let (Module { functions, body }, errors) = parse_top_level(tokens, filename);
+    let module = Module {
+        functions,
+        body: top_level_expression(body.kind, body.location, body.end_location),
+    };
+    (module, errors)
+}
+
+// Like `parse_module`, but the body is always the block of top-level
+// expressions, even when there is only one. Lets the REPL tell `var x = 1`
+// apart from `{ var x = 1 }`.
+pub fn parse_input(tokens: Vec<Token>, filename: &str) -> Result<Module, ParseError> {
+    let (module, errors) = parse_top_level(tokens, filename);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(module),
+    }
+}
+
+fn parse_top_level(tokens: Vec<Token>, filename: &str) -> (Module, Vec<ParseError>) {
+    let mut parser = Parser::new(tokens, filename);
+    if parser.peek().is_none() {
+        let error = parser.unexpected("an expression");
+        let location = error.location().clone();
+        let body = Expression::new(ExpressionKind::UnitLiteral, location.clone(), location);
+        let module = Module {
+            functions: Vec::new(),
+            body,
+        };
+        return (module, vec![error]);
+    }
+    let location = parser.current_location();
+    let mut functions = Vec::new();
+    let kind = parser.parse_sequence(None, Some(&mut functions));
+    let module = Module {
+        functions,
+        body: parser.ending_here(kind, location),
+    };
+    (module, parser.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse::*;
+    use crate::tokenizer::tokenize;
+
+    fn parse_source(source: &str) -> Result<Expression, ParseError> {
+        parse(tokenize(source.to_string()).unwrap())
+    }
+
+    fn parse_to_string(source: &str) -> String {
+        parse_source(source).unwrap().to_string()
+    }
+
+    #[test]
+    fn literals_and_identifiers() {
+        assert_eq!(parse_to_string("123 "), "123");
+        assert_eq!(parse_to_string("true "), "true");
+        assert_eq!(parse_to_string("false "), "false");
+        assert_eq!(parse_to_string("unit "), "unit");
+        assert_eq!(parse_to_string("some_name "), "some_name");
+    }
+
+    #[test]
+    fn binary_operator_precedence() {
+        assert_eq!(parse_to_string("1 + 2 * 3 "), "(1 + (2 * 3))");
+        assert_eq!(parse_to_string("1 * 2 + 3 "), "((1 * 2) + 3)");
+        assert_eq!(parse_to_string("1 + 2 % 3 "), "(1 + (2 % 3))");
+        assert_eq!(parse_to_string("-a % b"), "((-a) % b)");
+        assert_eq!(parse_to_string("a < b + 1 "), "(a < (b + 1))");
+        assert_eq!(parse_to_string("a == b < c "), "(a == (b < c))");
+        assert_eq!(parse_to_string("a != b >= c / d "), "(a != (b >= (c / d)))");
+    }
+
+    #[test]
+    fn binary_operators_are_left_associative() {
+        assert_eq!(parse_to_string("1 - 2 - 3 "), "((1 - 2) - 3)");
+        assert_eq!(parse_to_string("a / b * c "), "((a / b) * c)");
+        assert_eq!(parse_to_string("a % b * c / d"), "(((a % b) * c) / d)");
+    }
+
+    #[test]
+    fn boolean_operators() {
+        assert_eq!(parse_to_string("a or b and c"), "(a or (b and c))");
+        assert_eq!(parse_to_string("a and b or c"), "((a and b) or c)");
+        assert_eq!(parse_to_string("a or b or c"), "((a or b) or c)");
+        assert_eq!(
+            parse_to_string("a < 1 and b == c or d"),
+            "(((a < 1) and (b == c)) or d)"
+        );
+        assert_eq!(parse_to_string("x = a or b"), "(x = (a or b))");
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(parse_to_string("-1"), "(-1)");
+        assert_eq!(parse_to_string("not a"), "(not a)");
+        assert_eq!(parse_to_string("- -a"), "(-(-a))");
+        assert_eq!(parse_to_string("not not a"), "(not (not a))");
+        assert_eq!(parse_to_string("1 - -2"), "(1 - (-2))");
+        assert_eq!(parse_to_string("-a * b"), "((-a) * b)");
+        assert_eq!(parse_to_string("not a and b"), "((not a) and b)");
+        assert_eq!(parse_to_string("-f(x)"), "(-f(x))");
+        assert!(parse_source("not").is_err());
+        assert!(parse_source("1 not 2").is_err());
+    }
+
+    #[test]
+    fn assignment_is_right_associative() {
+        assert_eq!(parse_to_string("a = b = 1 + 2 "), "(a = (b = (1 + 2)))");
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(parse_to_string("( 1 + 2 ) * 3 "), "((1 + 2) * 3)");
+        assert_eq!(parse_to_string("1 - ( 2 - 3 ) "), "(1 - (2 - 3))");
+    }
+
+    #[test]
+    fn function_calls() {
+        assert_eq!(parse_to_string("f ( ) "), "f()");
+        assert_eq!(parse_to_string("f ( 1 , a + b ) "), "f(1, (a + b))");
+        assert_eq!(parse_to_string("f ( g ( x ) ) * 2 "), "(f(g(x)) * 2)");
+    }
+
+    #[test]
+    fn nodes_carry_location_of_first_token() {
+        let tokens = tokenize("a +\n b ".to_string()).unwrap();
+        let expression = parse(tokens.clone()).unwrap();
+        assert_eq!(&expression.location, tokens[0].location());
+        match expression.kind {
+            ExpressionKind::BinaryOp { right, .. } => {
+                assert_eq!(&right.location, tokens[2].location());
+            }
+            _ => panic!("expected a binary operation"),
+        }
+    }
+
+    #[test]
+    fn nodes_end_after_their_last_token() {
+        let tokens = tokenize(
+            "{ f(a, 1) }
++ (b)"
+                .to_string(),
+        )
+        .unwrap();
+        let expression = parse(tokens.clone()).unwrap();
+        assert_eq!(&expression.end_location, tokens[11].end_location());
+        assert_eq!(expression.span().length(), 17);
+        match expression.kind {
+            ExpressionKind::BinaryOp { left, right, .. } => {
+                assert_eq!(&left.end_location, tokens[7].end_location());
+                // Parentheses are not part of the inner expression
+                assert_eq!(right.span().length(), 1);
+            }
+            _ => panic!("expected a binary operation"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse_source("1 + ").unwrap_err();
+        assert_eq!(
+            error.message(),
+            "expected an expression, found end of input"
+        );
+        let error = parse_source("1 2 ").unwrap_err();
+        assert_eq!(error.message(), "expected ';', found '2'");
+        let error = parse_source("1 = 2 ").unwrap_err();
+        assert_eq!(
+            error.message(),
+            "left side of an assignment must be a variable name"
+        );
+        assert!(parse_source("( 1 + 2 ").is_err());
+        assert!(parse_source("f ( 1 , ) ").is_err());
+        assert!(parse_source("").is_err());
+    }
+
+    #[test]
+    fn error_recovery() {
+        let recover = |source: &str| {
+            let (module, errors) =
+                parse_module_recovering(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE);
+            let errors: Vec<String> = errors
+                .iter()
+                .map(|error| error.message().to_string())
+                .collect();
+            (module.to_string(), errors)
+        };
+        assert_eq!(
+            recover("a b; c"),
+            ("c".to_string(), vec!["expected ';', found 'b'".to_string()])
+        );
+        assert_eq!(
+            recover("{ 1 + ; x } }; f(,); y"),
+            (
+                "{{x}; y}".to_string(),
+                vec![
+                    "expected an expression, found ';'".to_string(),
+                    "expected an expression, found '}'".to_string(),
+                    "expected an expression, found ','".to_string(),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("fun f(a Int) { a } fun g() { 1 2 } g()"),
+            (
+                "fun g() {}\ng()".to_string(),
+                vec![
+                    "expected ':', found 'Int'".to_string(),
+                    "expected ';', found '2'".to_string(),
+                ]
+            )
+        );
+        assert_eq!(recover("1; 2").1, Vec::<String>::new());
+        assert_eq!(
+            recover("").1,
+            ["expected an expression, found end of input"]
+        );
+    }
+
+    #[test]
+    fn input_keeps_the_top_level_block() {
+        let input = |source: &str| {
+            parse_input(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(input("var x = 1"), "{(var x = 1)}");
+        assert_eq!(input("{ var x = 1 }"), "{{(var x = 1)}}");
+        assert_eq!(input("fun f() {} f()"), "fun f() {}\n{f()}");
+    }
+
+    #[test]
+    fn error_diagnostics() {
+        let source = "f(1\n  while)";
+        assert_eq!(
+            parse_source(source)
+                .unwrap_err()
+                .diagnostic()
+                .render(source, false),
+            "error: expected ')', found 'while'
+ --> <input>:2:3
+  |
+2 |   while)
+  |   ^^^^^
+"
+        );
+        let source = "1 +";
+        assert_eq!(
+            parse_source(source)
+                .unwrap_err()
+                .diagnostic()
+                .render(source, false),
+            "error: expected an expression, found end of input
+ --> <input>:1:4
+  |
+1 | 1 +
+  |    ^
+"
+        );
+    }
+
+    #[test]
+    fn end_of_input_location() {
+        let end_of_input = |source: &str| {
+            parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE)
+                .unwrap_err()
+                .location()
+                .to_string()
+        };
+        assert_eq!(end_of_input("{{{"), "<input>:1:4");
+        assert_eq!(end_of_input("fun"), "<input>:1:4");
+        assert_eq!(end_of_input("f(\n  1,"), "<input>:2:5");
+        // Such as a file with only comments
+        let error = parse_module(Vec::new(), "empty.scl").unwrap_err();
+        assert_eq!(
+            error.diagnostic().render("// nothing", false),
+            "error: expected an expression, found end of input
+ --> empty.scl:1:1
+  |
+1 | // nothing
+  | ^
+"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(parse_to_string("{ } "), "{}");
+        assert_eq!(parse_to_string("{ a } "), "{a}");
+        assert_eq!(parse_to_string("{ a ; } "), "{a;}");
+        assert_eq!(
+            parse_to_string("{ a ; b = 1 ; f ( b ) } "),
+            "{a; (b = 1); f(b)}"
+        );
+        assert_eq!(parse_to_string("x = { a ; b } + 1 "), "(x = ({a; b} + 1))");
+    }
+
+    #[test]
+    fn semicolon_can_be_left_out_after_block() {
+        assert_eq!(parse_to_string("{ { a } { b } } "), "{{a}; {b}}");
+        assert_eq!(parse_to_string("{ { a } b } "), "{{a}; b}");
+        assert_eq!(
+            parse_to_string("{ if a then { b } c } "),
+            "{(if a then {b}); c}"
+        );
+        assert_eq!(
+            parse_to_string("{ while a do { b } c ; } "),
+            "{(while a do {b}); c;}"
+        );
+        assert!(parse_source("{ a b } ").is_err());
+        assert!(parse_source("{ if a then b c } ").is_err());
+    }
+
+    #[test]
+    fn if_expressions() {
+        assert_eq!(parse_to_string("if a then b "), "(if a then b)");
+        assert_eq!(
+            parse_to_string("if a < 1 then b else c + 1 "),
+            "(if (a < 1) then b else (c + 1))"
+        );
+        assert_eq!(
+            parse_to_string("if a then b else if c then d else e "),
+            "(if a then b else (if c then d else e))"
+        );
+        assert_eq!(
+            parse_to_string("1 + if a then 2 else 3 "),
+            "(1 + (if a then 2 else 3))"
+        );
+        assert!(parse_source("if a b ").is_err());
+        assert!(parse_source("if a then ").is_err());
+    }
+
+    #[test]
+    fn while_expressions() {
+        assert_eq!(
+            parse_to_string("while a > 0 do a = a - 1 "),
+            "(while (a > 0) do (a = (a - 1)))"
+        );
+        assert_eq!(
+            parse_to_string("while true do { f ( ) ; } "),
+            "(while true do {f();})"
+        );
+        assert!(parse_source("while a { b } ").is_err());
+    }
+
+    #[test]
+    fn top_level_sequence() {
+        assert_eq!(parse_to_string("a ; b "), "{a; b}");
+        assert_eq!(parse_to_string("a ; b ; "), "{a; b;}");
+        assert_eq!(parse_to_string("{ a } b "), "{{a}; b}");
+    }
+
+    #[test]
+    fn var_declarations() {
+        assert_eq!(parse_to_string("var x = 1 "), "(var x = 1)");
+        assert_eq!(parse_to_string("const x = 1 + 2 "), "(const x = (1 + 2))");
+        assert_eq!(
+            parse_to_string("var x : Int = 1 ; x "),
+            "{(var x: Int = 1); x}"
+        );
+        assert_eq!(
+            parse_to_string("{ var b : Bool = true ; var c = { 1 } c } "),
+            "{(var b: Bool = true); (var c = {1}); c}"
+        );
+        assert!(parse_source("var = 1 ").is_err());
+        assert!(parse_source("var x ").is_err());
+        assert!(parse_source("var x : = 1 ").is_err());
+        assert!(parse_source("var if = 1 ").is_err());
+    }
+
+    #[test]
+    fn var_declarations_in_expression_position() {
+        let tokens = tokenize("if a then var x = 1 ".to_string()).unwrap();
+        let error = parse(tokens.clone()).unwrap_err();
+        assert_eq!(error.location(), tokens[3].location());
+        assert_eq!(
+            error.message(),
+            "'var' declarations are only allowed directly inside a block or at the top level"
+        );
+        assert!(parse_source("1 + const x = 1 ").is_err());
+        assert!(parse_source("f ( var x = 1 ) ").is_err());
+        assert!(parse_source("x = var y = 1 ").is_err());
+    }
+
+    #[test]
+    fn keywords_are_not_identifiers() {
+        assert!(parse_source("then ").is_err());
+        assert!(parse_source("else = 1 ").is_err());
+    }
+
+    fn parse_module_to_string(source: &str) -> String {
+        parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn function_definitions() {
+        assert_eq!(
+            parse_module_to_string("fun f(a: Int, b: Bool): Int { a } f(1, true)"),
+            "fun f(a: Int, b: Bool): Int {a}\nf(1, true)"
+        );
+        assert_eq!(
+            parse_module_to_string("var x = 1; fun g() { print_int(x) } fun h(x: Int) {}"),
+            "fun g() {print_int(x)}\nfun h(x: Int) {}\n{(var x = 1);}"
+        );
+        let module =
+            parse_module(tokenize("fun f() {}".to_string()).unwrap(), UNNAMED_SOURCE).unwrap();
+        assert_eq!(module.functions[0].location.col(), 1);
+        assert_eq!(module.body.to_string(), "{}");
+    }
+
+    #[test]
+    fn invalid_function_definitions() {
+        let error = |source: &str| {
+            parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE)
+                .unwrap_err()
+                .message()
+                .to_string()
+        };
+        assert_eq!(error("fun f(a) {}"), "expected ':', found ')'");
+        assert_eq!(error("fun f(a: Int) a"), "expected '{', found 'a'");
+        assert_eq!(error("fun (a: Int) {}"), "expected a name, found '('");
+        assert_eq!(
+            error("{ fun f() {} }"),
+            "function definitions are only allowed at the top level"
+        );
+        assert_eq!(
+            parse_source("fun f() {}").unwrap_err().message(),
+            "function definitions are only allowed at the top level"
+        );
+    }
+
+    #[test]
+    fn return_expressions() {
+        assert_eq!(parse_to_string("return 1 + 2"), "(return (1 + 2))");
+        assert_eq!(parse_to_string("return"), "(return)");
+        assert_eq!(parse_to_string("{ return; }"), "{(return);}");
+        assert_eq!(parse_to_string("{ return }"), "{(return)}");
+        assert_eq!(
+            parse_to_string("if a then return else b"),
+            "(if a then (return) else b)"
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(
+            parse_to_string("while a do { if b then break; continue }"),
+            "(while a do {(if b then (break)); (continue)})"
+        );
+        assert_eq!(
+            parse_to_string("while true do break x + 1"),
+            "(while true do (break (x + 1)))"
+        );
+        assert_eq!(parse_to_string("f(break, 1)"), "f((break), 1)");
+    }
+}
@@ -0,0 +1,384 @@
+use std::io::{self, Write};
+
+use crate::cli::token_line;
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::{InputSource, Interpreter};
+use crate::ir::IrGenerator;
+use crate::parser::{parse_input, parse_module, ExpressionKind, Module};
+use crate::tokenizer::{tokenize_named, tokenize_recovering, Punctuation, TokenType};
+use crate::typechecker::{Type, TypeChecker};
+
+// Name used in locations for the entered code
+const REPL_NAME: &str = "<repl>";
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+const HELP: &str = ":tokens <code>  print the tokens
+:ast <code>     print the syntax tree
+:type <code>    print the type without running the code
+:ir <code>      print the intermediate representation
+:help           print this help
+:quit           exit, as does the end of input";
+
+// An error together with the code its location points into
+#[derive(Debug)]
+pub struct ReplError {
+    diagnostic: Box<Diagnostic>,
+    source: String,
+}
+
+impl ReplError {
+    fn new(diagnostic: Diagnostic, source: &str) -> ReplError {
+        ReplError {
+            diagnostic: Box::new(diagnostic),
+            source: source.to_string(),
+        }
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.diagnostic
+    }
+
+    pub fn render(&self, colour: bool) -> String {
+        self.diagnostic.render(&self.source, colour)
+    }
+}
+
+// Keeps the variables and functions of earlier inputs around. An input that
+// fails to type check or run leaves them as they were.
+pub struct Repl {
+    checker: TypeChecker,
+    interpreter: Interpreter,
+    // Variables and functions defined by earlier inputs, globals as far as
+    // `:ir` is concerned
+    globals: Vec<String>,
+    functions: Vec<String>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
+
+fn parse(code: &str) -> Result<Module, ReplError> {
+    let tokens = tokenize_named(code.to_string(), REPL_NAME)
+        .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+    parse_input(tokens, REPL_NAME).map_err(|error| ReplError::new(error.diagnostic(), code))
+}
+
+// The functions of an input
+fn defined_functions(module: &Module) -> Vec<String> {
+    module
+        .functions
+        .iter()
+        .map(|function| function.name.clone())
+        .collect()
+}
+
+// The top-level variables of an input
+fn defined_variables(module: &Module) -> Vec<String> {
+    let mut names = Vec::new();
+    if let ExpressionKind::Block {
+        expressions,
+        result,
+    } = &module.body.kind
+    {
+        for expression in expressions.iter().chain(result.as_deref()) {
+            if let ExpressionKind::VarDeclaration { name, .. } = &expression.kind {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            checker: TypeChecker::new(),
+            interpreter: Interpreter::new(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    // Runs code or a `:command` and returns what to print
+    pub fn handle(&mut self, input: &str) -> Result<String, ReplError> {
+        let input = input.trim();
+        let command = match input.strip_prefix(':') {
+            Some(command) => command,
+            None => return self.evaluate(input),
+        };
+        let (name, code) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        match name {
+            "tokens" => {
+                let tokens = tokenize_named(code.to_string(), REPL_NAME)
+                    .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+                let lines: Vec<String> = tokens.iter().map(token_line).collect();
+                Ok(lines.join("\n"))
+            }
+            "ast" => {
+                let tokens = tokenize_named(code.to_string(), REPL_NAME)
+                    .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+                let module = parse_module(tokens, REPL_NAME)
+                    .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+                Ok(module.to_string())
+            }
+            "type" => Ok(self.check(code)?.1.to_string()),
+            "ir" => self.ir(code),
+            "help" => Ok(HELP.to_string()),
+            _ => Err(ReplError::new(
+                Diagnostic::new(format!("unknown command ':{}'", name))
+                    .with_note("':help' lists the commands"),
+                input,
+            )),
+        }
+    }
+
+    // Type checks with a copy of the environment, which is returned for
+    // keeping if the code goes on to run successfully
+    fn check(&self, code: &str) -> Result<(Module, Type, TypeChecker), ReplError> {
+        let mut module = parse(code)?;
+        let mut checker = self.checker.clone();
+        let ty = checker
+            .check_input(&mut module)
+            .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+        Ok((module, ty, checker))
+    }
+
+    fn evaluate(&mut self, code: &str) -> Result<String, ReplError> {
+        let (module, ty, checker) = self.check(code)?;
+        let mut interpreter = self.interpreter.clone();
+        let value = interpreter
+            .evaluate_input(&module)
+            .map_err(|error| ReplError::new(error.diagnostic(), code))?;
+        self.checker = checker;
+        self.interpreter = interpreter;
+        self.globals.extend(defined_variables(&module));
+        self.functions.extend(defined_functions(&module));
+        if ty == Type::Unit {
+            return Ok(String::new());
+        }
+        Ok(format!("{} : {}", value, ty))
+    }
+
+    fn ir(&self, code: &str) -> Result<String, ReplError> {
+        let (module, _, _) = self.check(code)?;
+        let new_generator = || {
+            let mut generator = IrGenerator::new();
+            for name in self.globals.iter().chain(defined_variables(&module).iter()) {
+                generator.declare_global(name);
+            }
+            for name in self
+                .functions
+                .iter()
+                .chain(defined_functions(&module).iter())
+            {
+                generator.declare_function(name);
+            }
+            generator
+        };
+        let mut output = String::new();
+        for function in &module.functions {
+            output += &new_generator().generate_function(function).to_string();
+        }
+        output += &new_generator().generate("main", &module.body).to_string();
+        Ok(output.trim_end().to_string())
+    }
+}
+
+// Opening minus closing brackets, ignoring what does not tokenize
+fn bracket_depth(code: &str) -> i64 {
+    let (tokens, _) = tokenize_recovering(code.to_string(), REPL_NAME);
+    tokens
+        .iter()
+        .map(|token| match token.token_type() {
+            TokenType::Punctuation(Punctuation::BracketOpen | Punctuation::CurlyOpen) => 1,
+            TokenType::Punctuation(Punctuation::BracketClose | Punctuation::CurlyClose) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// Reads lines for as long as there are unclosed brackets. None at the end of
+// input.
+fn read_input(input: &InputSource, out: &mut dyn Write) -> io::Result<Option<String>> {
+    let mut code = String::new();
+    loop {
+        let prompt = if code.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        write!(out, "{}", prompt)?;
+        out.flush()?;
+        match input() {
+            Some(line) => code += &line,
+            None if code.is_empty() => {
+                writeln!(out)?;
+                return Ok(None);
+            }
+            None => return Ok(Some(code)),
+        }
+        if !code.ends_with('\n') {
+            code.push('\n');
+        }
+        if bracket_depth(&code) <= 0 {
+            return Ok(Some(code));
+        }
+    }
+}
+
+// Reads inputs until the end of input or `:quit`, writing the results and
+// errors to `out`
+pub fn run(input: InputSource, out: &mut dyn Write, colour: bool) -> io::Result<()> {
+    let mut repl = Repl::new();
+    while let Some(code) = read_input(&input, out)? {
+        match code.trim() {
+            "" => continue,
+            ":quit" => break,
+            _ => {}
+        }
+        match repl.handle(&code) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => writeln!(out, "{}", output)?,
+            Err(error) => write!(out, "{}", error.render(colour))?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use crate::repl::*;
+
+    fn output(repl: &mut Repl, input: &str) -> String {
+        repl.handle(input).unwrap()
+    }
+
+    fn error(repl: &mut Repl, input: &str) -> String {
+        repl.handle(input)
+            .unwrap_err()
+            .diagnostic()
+            .message()
+            .to_string()
+    }
+
+    #[test]
+    fn environment_persists() {
+        let mut repl = Repl::new();
+        assert_eq!(output(&mut repl, "var x = 20; var y = x + 1"), "");
+        assert_eq!(output(&mut repl, "x + y"), "41 : Int");
+        assert_eq!(output(&mut repl, "x = x + 1; x < y"), "false : Bool");
+        assert_eq!(output(&mut repl, "{ var z = 1; z }"), "1 : Int");
+        assert_eq!(error(&mut repl, "z"), "undefined variable 'z'");
+        assert_eq!(output(&mut repl, "fun double(a: Int): Int { a * 2 }"), "");
+        assert_eq!(output(&mut repl, "double(x)"), "42 : Int");
+        assert_eq!(
+            output(&mut repl, "double"),
+            "<function double> : (Int) => Int"
+        );
+        assert_eq!(output(&mut repl, "fun double(a: Int): Int { a + a }"), "");
+        assert_eq!(
+            error(&mut repl, "fun double(a: Bool): Bool { a }"),
+            "'double' is already defined"
+        );
+    }
+
+    #[test]
+    fn failed_inputs_change_nothing() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            error(&mut repl, "var a = 1; var b = a + true"),
+            "operator '+' expects operands of type Int, got Int and Bool"
+        );
+        assert_eq!(error(&mut repl, "var a = 1; a = 1 / 0"), "division by zero");
+        assert_eq!(output(&mut repl, "var a = 2; a"), "2 : Int");
+        assert_eq!(error(&mut repl, "a = 5; 1 / 0"), "division by zero");
+        assert_eq!(output(&mut repl, "a"), "2 : Int");
+    }
+
+    #[test]
+    fn commands() {
+        let mut repl = Repl::new();
+        output(&mut repl, "var x = 1");
+        assert_eq!(
+            output(&mut repl, ":tokens x + 1"),
+            "1:1\tIdentifier\tx\n1:3\tOperator(Plus)\t+\n1:5\tIntLiteral\t1"
+        );
+        assert_eq!(output(&mut repl, ":ast x = 2 * x"), "(x = (2 * x))");
+        assert_eq!(output(&mut repl, ":type x < 2"), "Bool");
+        assert_eq!(output(&mut repl, ":type var y = 1"), "Unit");
+        assert_eq!(error(&mut repl, "y"), "undefined variable 'y'");
+        assert_eq!(
+            output(&mut repl, ":ir print_int(x)"),
+            "main():
+    Call(print_int, [x], x1)
+    Return()"
+        );
+        output(&mut repl, "fun f(): Int { x }");
+        assert_eq!(
+            output(&mut repl, ":ir { var g = f; g() }"),
+            "main():
+    LoadFunction(f, x1)
+    Copy(x1, g)
+    Call(g, [], x2)
+    Return()"
+        );
+        assert_eq!(
+            error(&mut repl, ":type 1 +"),
+            "expected an expression, found end of input"
+        );
+        assert_eq!(
+            error(&mut repl, ":frobnicate"),
+            "unknown command ':frobnicate'"
+        );
+        assert!(output(&mut repl, ":help").contains(":tokens"));
+    }
+
+    #[test]
+    fn errors_point_into_the_code() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            repl.handle(":type 1 + true").unwrap_err().render(false),
+            "error: operator '+' expects operands of type Int, got Int and Bool
+ --> <repl>:1:1
+  |
+1 | 1 + true
+  | ^^^^^^^^
+"
+        );
+    }
+
+    fn run_lines(lines: &[&str]) -> String {
+        let lines: RefCell<VecDeque<String>> =
+            RefCell::new(lines.iter().map(|line| format!("{}\n", line)).collect());
+        let input: InputSource = Rc::new(move || lines.borrow_mut().pop_front());
+        let mut out = Vec::new();
+        run(input, &mut out, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn multiline_input() {
+        assert_eq!(
+            run_lines(&["var x = {", "  1 + (", "2)", "}", "", "x * 2", "1 +"]),
+            "> ... ... ... > > 6 : Int
+> error: expected an expression, found end of input
+ --> <repl>:1:4
+  |
+1 | 1 +
+  |    ^
+> \n"
+        );
+        assert_eq!(run_lines(&[":quit", "1"]), "> ");
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::driver::compile_to_bytes;
+use crate::tokenizer::UNNAMED_SOURCE;
+
+// Answers a single request of the protocol:
+//   {"command": "ping"}                  -> {}
+//   {"command": "compile", "code": "..."} -> {"program": "<base64 executable>"}
+// Anything that goes wrong is reported as {"error": "..."}.
+pub fn handle_request(request: &str) -> Value {
+    let request: Value = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(error) => return json!({ "error": format!("invalid request: {}", error) }),
+    };
+    match request["command"].as_str() {
+        Some("ping") => json!({}),
+        Some("compile") => match request["code"].as_str() {
+            Some(code) => match compile_to_bytes(code.to_string(), UNNAMED_SOURCE) {
+                Ok(program) => json!({ "program": STANDARD.encode(program) }),
+                Err(error) => json!({ "error": error.to_string() }),
+            },
+            None => json!({ "error": "compile requires 'code' to be a string" }),
+        },
+        Some(command) => json!({ "error": format!("unknown command '{}'", command) }),
+        None => json!({ "error": "'command' must be a string" }),
+    }
+}
+
+// The client sends the request and shuts down its side of the connection,
+// then waits for the response
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    let mut request = String::new();
+    stream.read_to_string(&mut request)?;
+    let response = handle_request(&request);
+    stream.write_all(response.to_string().as_bytes())?;
+    stream.flush()
+}
+
+// Serves until the process is stopped, each connection on its own thread
+pub fn serve(host: &str, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind((host, port))?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        // A failed accept only loses that connection
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            if let Err(error) = handle_connection(stream) {
+                eprintln!("error: {}", error);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Shutdown;
+
+    use crate::server::*;
+
+    #[test]
+    fn ping() {
+        assert_eq!(handle_request(r#"{"command": "ping"}"#), json!({}));
+    }
+
+    #[test]
+    fn compile() {
+        let response = handle_request(r#"{"command": "compile", "code": "print_int(1)"}"#);
+        let program = STANDARD
+            .decode(response["program"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(&program[..4], b"\x7fELF");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            handle_request(r#"{"command": "compile", "code": "1 + true"}"#),
+            json!({
+                "error": "operator '+' expects operands of type Int, got Int and Bool at <input>:1:1"
+            })
+        );
+        assert_eq!(
+            handle_request(r#"{"command": "compile"}"#),
+            json!({ "error": "compile requires 'code' to be a string" })
+        );
+        assert_eq!(
+            handle_request(r#"{"command": "run"}"#),
+            json!({ "error": "unknown command 'run'" })
+        );
+        assert_eq!(
+            handle_request(r#"[]"#),
+            json!({ "error": "'command' must be a string" })
+        );
+        assert!(handle_request("{")["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request: "));
+    }
+
+    #[test]
+    fn over_tcp() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream).unwrap();
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(br#"{"command": "ping"}"#).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        assert_eq!(response, "{}");
+    }
+}
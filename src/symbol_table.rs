@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+// Nested scopes mapping names to values of type T. The innermost scope is
+// the last one. Shared by the passes that need to resolve variable names.
+#[derive(Clone, Debug)]
+pub struct SymbolTable<T> {
+    scopes: Vec<HashMap<String, T>>,
+}
+
+impl<T> Default for SymbolTable<T> {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
+
+impl<T> SymbolTable<T> {
+    // Starts with a single, outermost scope
+    pub fn new() -> SymbolTable<T> {
+        SymbolTable {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    // Returns false if the name is already declared in the innermost scope
+    pub fn declare(&mut self, name: &str, value: T) -> bool {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(name) {
+            return false;
+        }
+        scope.insert(name.to_string(), value);
+        true
+    }
+
+    // Hides every scope but the outermost one, for example while checking a
+    // function body that can only see globals
+    pub fn take_inner_scopes(&mut self) -> Vec<HashMap<String, T>> {
+        self.scopes.split_off(1)
+    }
+
+    // Puts back scopes returned by `take_inner_scopes`
+    pub fn restore_inner_scopes(&mut self, scopes: Vec<HashMap<String, T>>) {
+        self.scopes.truncate(1);
+        self.scopes.extend(scopes);
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&T> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub fn lookup_mut(&mut self, name: &str) -> Option<&mut T> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol_table::*;
+
+    #[test]
+    fn inner_scope_shadows_outer() {
+        let mut table: SymbolTable<i64> = SymbolTable::new();
+        assert!(table.declare("x", 1));
+        table.push_scope();
+        assert_eq!(table.lookup("x"), Some(&1));
+        assert!(table.declare("x", 2));
+        assert_eq!(table.lookup("x"), Some(&2));
+        table.pop_scope();
+        assert_eq!(table.lookup("x"), Some(&1));
+    }
+
+    #[test]
+    fn redeclaration_in_same_scope() {
+        let mut table: SymbolTable<i64> = SymbolTable::new();
+        assert!(table.declare("x", 1));
+        assert!(!table.declare("x", 2));
+        assert_eq!(table.lookup("x"), Some(&1));
+    }
+
+    #[test]
+    fn lookup_mut_finds_nearest() {
+        let mut table: SymbolTable<i64> = SymbolTable::new();
+        table.declare("x", 1);
+        table.push_scope();
+        *table.lookup_mut("x").unwrap() = 5;
+        table.pop_scope();
+        assert_eq!(table.lookup("x"), Some(&5));
+        assert_eq!(table.lookup("y"), None);
+        table.pop_scope();
+        assert_eq!(table.lookup("x"), Some(&5));
+    }
+
+    #[test]
+    fn inner_scopes_can_be_hidden() {
+        let mut table: SymbolTable<i64> = SymbolTable::new();
+        table.declare("global", 1);
+        table.push_scope();
+        table.declare("local", 2);
+        let hidden = table.take_inner_scopes();
+        assert_eq!(table.lookup("local"), None);
+        assert_eq!(table.lookup("global"), Some(&1));
+        table.push_scope();
+        table.declare("parameter", 3);
+        table.restore_inner_scopes(hidden);
+        assert_eq!(table.lookup("parameter"), None);
+        assert_eq!(table.lookup("local"), Some(&2));
+    }
+}
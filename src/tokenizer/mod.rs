@@ -1,4 +1,9 @@
 mod token_regex;
+mod token_type;
 mod tokenize;
 pub use token_regex::*;
-pub use tokenize::tokenize;
+pub use token_type::*;
+pub use tokenize::{
+    tokenize, tokenize_file, tokenize_named, tokenize_recovering, tokenize_with_comments, LexError,
+    Location, Token, TokenizeFileError, UNNAMED_SOURCE,
+};
@@ -1,34 +1,48 @@
-pub const ASSIGN_REGEX_STR: &str = r"^=$";
-pub const ASTERIX_REGEX_STR: &str = r"^\*$";
-pub const COMMA_REGEX_STR: &str = r"^,$";
-pub const COMMENT_REGEX_STR: &str = r"^(//|#)(.*)\n$";
-pub const CONST_REGEX_STR: &str = r"^const$";
-pub const CURLY_CLOSE_REGEX_STR: &str = r"^\}$";
-pub const CURLY_OPEN_REGEX_STR: &str = r"^\{$";
-pub const BOOLEAN_REGEX_STR: &str = r"^(true|false)$";
-pub const BRACKET_CLOSE_REGEX_STR: &str = r"^\)$";
-pub const BRACKET_OPEN_REGEX_STR: &str = r"^\($";
-pub const DASH_REGEX_STR: &str = r"^/$";
-pub const ELSE_REGEX_STR: &str = r"^else$";
-pub const EQ_REGEX_STR: &str = r"^==$";
-pub const GREATER_REGEX_STR: &str = r"^>$";
-pub const GEQ_REGEX_STR: &str = r"^>=$";
-pub const IF_REGEX_STR: &str = r"^if$";
-pub const INTEGER_REGEX_STR: &str = r"^\d+$";
-pub const LESS_REGEX_STR: &str = r"^<$";
-pub const LEQ_REGEX_STR: &str = r"^<=$";
-pub const MINUS_REGEX_STR: &str = r"^-$";
-pub const NEQ_REGEX_STR: &str = r"^!=$";
-pub const NEW_LINE_REGEX_STR: &str = r"^\n$";
-pub const PLUS_REGEX_STR: &str = r"^\+$";
-pub const SEMICOLON_REGEX_STR: &str = r"^;$";
-pub const UNIT_REGEX_STR: &str = r"^unit$";
-pub const VAR_REGEX_STR: &str = r"^var$";
-pub const VARIABLE_NAME_REGEX_STR: &str = r"^([a-z]|[A-Z]|_)([a-z]|[A-Z]|_|[0-9])*$";
-pub const WHILE_REGEX_STR: &str = r"^while$";
-pub const WHITESPACE_REGEX_STR: &str = r"^ $";
+// Every regex is anchored only to the start of the input, so the tokenizer can
+// find the longest token starting at its current position.
+pub const AND_REGEX_STR: &str = r"^and";
+pub const ASSIGN_REGEX_STR: &str = r"^=";
+pub const ASTERIX_REGEX_STR: &str = r"^\*";
+pub const COLON_REGEX_STR: &str = r"^:";
+pub const COMMA_REGEX_STR: &str = r"^,";
+pub const COMMENT_REGEX_STR: &str = r"^(//|#)[^\n]*";
+pub const CONST_REGEX_STR: &str = r"^const";
+pub const CONTINUE_REGEX_STR: &str = r"^continue";
+pub const CURLY_CLOSE_REGEX_STR: &str = r"^\}";
+pub const CURLY_OPEN_REGEX_STR: &str = r"^\{";
+pub const BOOLEAN_REGEX_STR: &str = r"^(true|false)";
+pub const BREAK_REGEX_STR: &str = r"^break";
+pub const BRACKET_CLOSE_REGEX_STR: &str = r"^\)";
+pub const BRACKET_OPEN_REGEX_STR: &str = r"^\(";
+pub const DO_REGEX_STR: &str = r"^do";
+pub const ELSE_REGEX_STR: &str = r"^else";
+pub const EQ_REGEX_STR: &str = r"^==";
+pub const FUN_REGEX_STR: &str = r"^fun";
+pub const GREATER_REGEX_STR: &str = r"^>";
+pub const GEQ_REGEX_STR: &str = r"^>=";
+pub const IF_REGEX_STR: &str = r"^if";
+pub const INTEGER_REGEX_STR: &str = r"^\d+";
+pub const LESS_REGEX_STR: &str = r"^<";
+pub const LEQ_REGEX_STR: &str = r"^<=";
+pub const MINUS_REGEX_STR: &str = r"^-";
+pub const NEQ_REGEX_STR: &str = r"^!=";
+pub const NEW_LINE_REGEX_STR: &str = r"^\n";
+pub const NOT_REGEX_STR: &str = r"^not";
+pub const OR_REGEX_STR: &str = r"^or";
+pub const PERCENT_REGEX_STR: &str = r"^%";
+pub const PLUS_REGEX_STR: &str = r"^\+";
+pub const RETURN_REGEX_STR: &str = r"^return";
+pub const SEMICOLON_REGEX_STR: &str = r"^;";
+pub const SLASH_REGEX_STR: &str = r"^/";
+pub const THEN_REGEX_STR: &str = r"^then";
+pub const UNIT_REGEX_STR: &str = r"^unit";
+pub const VAR_REGEX_STR: &str = r"^var";
+pub const VARIABLE_NAME_REGEX_STR: &str = r"^([a-z]|[A-Z]|_)([a-z]|[A-Z]|_|[0-9])*";
+pub const WHILE_REGEX_STR: &str = r"^while";
+pub const WHITESPACE_REGEX_STR: &str = r"^[ \t\r]+";
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::needless_return)]
 mod tests {
     use super::*;
 
@@ -44,6 +58,16 @@ mod tests {
         return regex.is_match(input);
     }
 
+    fn matched_prefix<'a>(regex_str: &str, input: &'a str) -> Option<&'a str> {
+        let regex = Regex::new(regex_str).unwrap();
+        regex.find(input).map(|found| found.as_str())
+    }
+
+    #[test]
+    fn and_regex() {
+        assert_eq!(regex_matches(AND_REGEX_STR, "and"), true);
+    }
+
     #[test]
     fn assign_regex() {
         assert_eq!(regex_matches(ASSIGN_REGEX_STR, "="), true);
@@ -54,6 +78,11 @@ mod tests {
         assert_eq!(regex_matches(ASTERIX_REGEX_STR, "*"), true);
     }
 
+    #[test]
+    fn colon_regex() {
+        assert_eq!(regex_matches(COLON_REGEX_STR, ":"), true);
+    }
+
     #[test]
     fn comma_regex() {
         assert_eq!(regex_matches(COMMA_REGEX_STR, ","), true);
@@ -66,6 +95,14 @@ mod tests {
         assert_eq!(regex_matches(COMMENT_REGEX_STR, SOME_VARIABLE_NAME), false);
         assert_eq!(regex_matches(COMMENT_REGEX_STR, SOME_COMMENT), true);
         assert_eq!(regex_matches(COMMENT_REGEX_STR, SOME_OTHER_COMMENT), true);
+        assert_eq!(
+            matched_prefix(COMMENT_REGEX_STR, "// comment\nx"),
+            Some("// comment")
+        );
+        assert_eq!(
+            matched_prefix(COMMENT_REGEX_STR, "# at the end"),
+            Some("# at the end")
+        );
     }
 
     #[test]
@@ -73,6 +110,11 @@ mod tests {
         assert_eq!(regex_matches(CONST_REGEX_STR, "const"), true);
     }
 
+    #[test]
+    fn continue_regex() {
+        assert_eq!(regex_matches(CONTINUE_REGEX_STR, "continue"), true);
+    }
+
     #[test]
     fn curly_close_regex() {
         assert_eq!(regex_matches(CURLY_CLOSE_REGEX_STR, "}"), true);
@@ -89,19 +131,24 @@ mod tests {
         assert_eq!(regex_matches(BOOLEAN_REGEX_STR, "false"), true);
     }
 
+    #[test]
+    fn break_regex() {
+        assert_eq!(regex_matches(BREAK_REGEX_STR, "break"), true);
+    }
+
     #[test]
     fn bracket_close_regex() {
-        assert_eq!(regex_matches(BRACKET_CLOSE_REGEX_STR, ")"), true);
+        assert!(regex_matches(BRACKET_CLOSE_REGEX_STR, ")"));
     }
 
     #[test]
     fn bracket_open_regex() {
-        assert_eq!(regex_matches(BRACKET_OPEN_REGEX_STR, "("), true);
+        assert!(regex_matches(BRACKET_OPEN_REGEX_STR, "("));
     }
 
     #[test]
-    fn dash_regex() {
-        assert_eq!(regex_matches(DASH_REGEX_STR, r"/"), true);
+    fn do_regex() {
+        assert_eq!(regex_matches(DO_REGEX_STR, "do"), true);
     }
 
     #[test]
@@ -114,6 +161,11 @@ mod tests {
         assert_eq!(regex_matches(EQ_REGEX_STR, "=="), true);
     }
 
+    #[test]
+    fn fun_regex() {
+        assert_eq!(regex_matches(FUN_REGEX_STR, "fun"), true);
+    }
+
     #[test]
     fn greater_regex() {
         assert_eq!(regex_matches(GREATER_REGEX_STR, ">"), true);
@@ -132,7 +184,8 @@ mod tests {
     #[test]
     fn integer_regex() {
         assert_eq!(regex_matches(INTEGER_REGEX_STR, "12347787897798"), true);
-        assert_eq!(regex_matches(INTEGER_REGEX_STR, "1233a12"), false);
+        assert_eq!(matched_prefix(INTEGER_REGEX_STR, "1233a12"), Some("1233"));
+        assert_eq!(regex_matches(INTEGER_REGEX_STR, "a12"), false);
     }
 
     #[test]
@@ -160,16 +213,46 @@ mod tests {
         assert_eq!(regex_matches(NEW_LINE_REGEX_STR, "\n"), true);
     }
 
+    #[test]
+    fn not_regex() {
+        assert_eq!(regex_matches(NOT_REGEX_STR, "not"), true);
+    }
+
+    #[test]
+    fn or_regex() {
+        assert_eq!(regex_matches(OR_REGEX_STR, "or"), true);
+    }
+
+    #[test]
+    fn percent_regex() {
+        assert_eq!(regex_matches(PERCENT_REGEX_STR, "%"), true);
+    }
+
     #[test]
     fn plus_regex() {
         assert_eq!(regex_matches(PLUS_REGEX_STR, "+"), true);
     }
 
+    #[test]
+    fn return_regex() {
+        assert_eq!(regex_matches(RETURN_REGEX_STR, "return"), true);
+    }
+
     #[test]
     fn semicolon_regex() {
         assert_eq!(regex_matches(SEMICOLON_REGEX_STR, ";"), true);
     }
 
+    #[test]
+    fn slash_regex() {
+        assert_eq!(regex_matches(SLASH_REGEX_STR, r"/"), true);
+    }
+
+    #[test]
+    fn then_regex() {
+        assert_eq!(regex_matches(THEN_REGEX_STR, "then"), true);
+    }
+
     #[test]
     fn unit_regex() {
         assert_eq!(regex_matches(UNIT_REGEX_STR, "unit"), true);
@@ -200,6 +283,7 @@ mod tests {
     #[test]
     fn whitespace_regex() {
         assert_eq!(regex_matches(WHITESPACE_REGEX_STR, " "), true);
+        assert_eq!(matched_prefix(WHITESPACE_REGEX_STR, " \t x"), Some(" \t "));
         assert_eq!(regex_matches(WHITESPACE_REGEX_STR, "\n"), false);
         assert_eq!(
             regex_matches(WHITESPACE_REGEX_STR, SOME_VARIABLE_NAME),
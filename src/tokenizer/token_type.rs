@@ -0,0 +1,129 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Keyword {
+    And,
+    Break,
+    Const,
+    Continue,
+    Do,
+    Else,
+    Fun,
+    If,
+    Not,
+    Or,
+    Return,
+    Then,
+    Var,
+    While,
+}
+
+impl Keyword {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::And => "and",
+            Keyword::Break => "break",
+            Keyword::Const => "const",
+            Keyword::Continue => "continue",
+            Keyword::Do => "do",
+            Keyword::Else => "else",
+            Keyword::Fun => "fun",
+            Keyword::If => "if",
+            Keyword::Not => "not",
+            Keyword::Or => "or",
+            Keyword::Return => "return",
+            Keyword::Then => "then",
+            Keyword::Var => "var",
+            Keyword::While => "while",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Assign,
+    Asterisk,
+    Eq,
+    Geq,
+    Greater,
+    Leq,
+    Less,
+    Minus,
+    Neq,
+    Percent,
+    Plus,
+    Slash,
+}
+
+impl Operator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Assign => "=",
+            Operator::Asterisk => "*",
+            Operator::Eq => "==",
+            Operator::Geq => ">=",
+            Operator::Greater => ">",
+            Operator::Leq => "<=",
+            Operator::Less => "<",
+            Operator::Minus => "-",
+            Operator::Neq => "!=",
+            Operator::Percent => "%",
+            Operator::Plus => "+",
+            Operator::Slash => "/",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Punctuation {
+    BracketClose,
+    BracketOpen,
+    Colon,
+    Comma,
+    CurlyClose,
+    CurlyOpen,
+    Semicolon,
+}
+
+impl Punctuation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Punctuation::BracketClose => ")",
+            Punctuation::BracketOpen => "(",
+            Punctuation::Colon => ":",
+            Punctuation::Comma => ",",
+            Punctuation::CurlyClose => "}",
+            Punctuation::CurlyOpen => "{",
+            Punctuation::Semicolon => ";",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenType {
+    BoolLiteral,
+    // Only produced by `tokenize_with_comments`
+    Comment,
+    Identifier,
+    IntLiteral,
+    Keyword(Keyword),
+    Operator(Operator),
+    Punctuation(Punctuation),
+    UnitLiteral,
+}
+
+// Describes what kind of token is expected, used in error messages
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenType::BoolLiteral => write!(f, "a boolean"),
+            TokenType::Comment => write!(f, "a comment"),
+            TokenType::Identifier => write!(f, "a name"),
+            TokenType::IntLiteral => write!(f, "an integer"),
+            TokenType::Keyword(keyword) => write!(f, "'{}'", keyword.as_str()),
+            TokenType::Operator(operator) => write!(f, "'{}'", operator.as_str()),
+            TokenType::Punctuation(punctuation) => write!(f, "'{}'", punctuation.as_str()),
+            TokenType::UnitLiteral => write!(f, "'unit'"),
+        }
+    }
+}
@@ -1,282 +1,363 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
 use regex::Regex;
 
+use crate::diagnostics::{Diagnostic, Span};
+
 use super::*;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Location {
     filename: String,
     line: usize,
     col: usize,
+    // Byte offset into the source
+    offset: usize,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum TokenType {
-    Identifier,
-    IntLiteral,
-    Operator,
-    Punctuation,
+impl Location {
+    // The beginning of the source with the given name
+    pub fn start(filename: &str) -> Location {
+        Location {
+            filename: filename.to_string(),
+            line: 1,
+            col: 1,
+            offset: 0,
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.filename, self.line, self.col)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     location: Location,
+    // Location right after the last character of the token
+    end_location: Location,
     token_type: TokenType,
     value: String,
 }
 
-fn move_indexes(substring_start_index: &mut usize, substring_end_index: &mut usize) {
-    *substring_start_index = *substring_end_index;
-    *substring_end_index = *substring_end_index + 1;
+impl Token {
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn end_location(&self) -> &Location {
+        &self.end_location
+    }
+
+    // Byte range of the token in the source
+    pub fn span(&self) -> Range<usize> {
+        self.location.offset..self.end_location.offset
+    }
+
+    pub fn token_type(&self) -> &TokenType {
+        &self.token_type
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    location: Location,
+    text: String,
+}
+
+impl LexError {
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::at(
+            format!("unexpected character '{}'", self.text),
+            Span::new(self.location.clone(), self.text.len()),
+        )
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unexpected character '{}' at {}",
+            self.text, self.location
+        )
+    }
+}
+
+impl std::error::Error for LexError {}
+
+#[derive(Debug)]
+pub enum TokenizeFileError {
+    Io(io::Error),
+    Lex(LexError),
+}
+
+impl fmt::Display for TokenizeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenizeFileError::Io(error) => write!(f, "{}", error),
+            TokenizeFileError::Lex(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TokenizeFileError {}
+
+impl From<io::Error> for TokenizeFileError {
+    fn from(error: io::Error) -> TokenizeFileError {
+        TokenizeFileError::Io(error)
+    }
 }
 
-// Check if character at given index is "empty"
-// "empty" means a space, newline, tab or string end
-fn char_is_empty(input: &String, index: usize) -> bool {
-    let next_char = input.chars().nth(index);
-    if next_char.is_some() {
-        let character = next_char.unwrap();
-        return (character == ' ') | (character == '\n') | (character == '\t');
-    } else {
-        // Index larger than input size
-        return true;
+impl From<LexError> for TokenizeFileError {
+    fn from(error: LexError) -> TokenizeFileError {
+        TokenizeFileError::Lex(error)
     }
 }
 
-fn handle_new_line(line: &mut usize, col: &mut usize) {
-    *line = *line + 1;
-    *col = 1;
+// A token is recognised by matching a regex at the current position. Rules
+// without a token type match text that is skipped.
+struct TokenRule {
+    regex: Regex,
+    token_type: Option<TokenType>,
 }
 
-fn handle_col(col: &mut usize, size: usize) {
-    *col = *col + size;
+fn rule(regex_str: &str, token_type: Option<TokenType>) -> TokenRule {
+    TokenRule {
+        regex: Regex::new(regex_str).unwrap(),
+        token_type,
+    }
 }
 
-pub fn tokenize(mut input: String) -> Vec<Token> {
-    let assign_regex = Regex::new(ASSIGN_REGEX_STR).unwrap();
-    let asterix_regex = Regex::new(ASTERIX_REGEX_STR).unwrap();
-    let comma_regex = Regex::new(COMMA_REGEX_STR).unwrap();
-    let comment_regex = Regex::new(COMMENT_REGEX_STR).unwrap();
-    let const_regex = Regex::new(CONST_REGEX_STR).unwrap();
-    let curly_close_regex = Regex::new(CURLY_CLOSE_REGEX_STR).unwrap();
-    let curly_open_regex = Regex::new(CURLY_OPEN_REGEX_STR).unwrap();
-    let boolean_regex = Regex::new(BOOLEAN_REGEX_STR).unwrap();
-    let bracket_close_regex = Regex::new(BRACKET_CLOSE_REGEX_STR).unwrap();
-    let bracket_open_regex = Regex::new(BRACKET_OPEN_REGEX_STR).unwrap();
-    let dash_regex = Regex::new(DASH_REGEX_STR).unwrap();
-    let else_regex = Regex::new(ELSE_REGEX_STR).unwrap();
-    let eq_regex = Regex::new(EQ_REGEX_STR).unwrap();
-    let greater_regex = Regex::new(GREATER_REGEX_STR).unwrap();
-    let geq_regex = Regex::new(GEQ_REGEX_STR).unwrap();
-    let if_regex = Regex::new(IF_REGEX_STR).unwrap();
-    let integer_regex = Regex::new(INTEGER_REGEX_STR).unwrap();
-    let less_regex = Regex::new(LESS_REGEX_STR).unwrap();
-    let leq_regex = Regex::new(LEQ_REGEX_STR).unwrap();
-    let minus_regex = Regex::new(MINUS_REGEX_STR).unwrap();
-    let neq_regex = Regex::new(NEQ_REGEX_STR).unwrap();
-    let new_line_regex = Regex::new(NEW_LINE_REGEX_STR).unwrap();
-    let plus_regex = Regex::new(PLUS_REGEX_STR).unwrap();
-    let semicolon_regex = Regex::new(SEMICOLON_REGEX_STR).unwrap();
-    let unit_regex = Regex::new(UNIT_REGEX_STR).unwrap();
-    let var_regex = Regex::new(VAR_REGEX_STR).unwrap();
-    let variable_name_regex = Regex::new(VARIABLE_NAME_REGEX_STR).unwrap();
-    let while_regex = Regex::new(WHILE_REGEX_STR).unwrap();
-    let whitespace_regex = Regex::new(WHITESPACE_REGEX_STR).unwrap();
-
-    let mut substring_start_index: usize = 0;
-    let mut substring_end_index: usize = 1;
+// When several rules match equally long text, the one listed first wins.
+// This makes keywords take precedence over variable names.
+fn token_rules() -> Vec<TokenRule> {
+    vec![
+        // Skipped, and comments too unless they are asked for
+        rule(NEW_LINE_REGEX_STR, None),
+        rule(WHITESPACE_REGEX_STR, None),
+        rule(COMMENT_REGEX_STR, Some(TokenType::Comment)),
+        // Rest alphabetically
+        rule(AND_REGEX_STR, Some(TokenType::Keyword(Keyword::And))),
+        rule(
+            ASSIGN_REGEX_STR,
+            Some(TokenType::Operator(Operator::Assign)),
+        ),
+        rule(
+            ASTERIX_REGEX_STR,
+            Some(TokenType::Operator(Operator::Asterisk)),
+        ),
+        rule(
+            COLON_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::Colon)),
+        ),
+        rule(
+            COMMA_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::Comma)),
+        ),
+        rule(CONST_REGEX_STR, Some(TokenType::Keyword(Keyword::Const))),
+        rule(
+            CONTINUE_REGEX_STR,
+            Some(TokenType::Keyword(Keyword::Continue)),
+        ),
+        rule(
+            CURLY_CLOSE_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::CurlyClose)),
+        ),
+        rule(
+            CURLY_OPEN_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::CurlyOpen)),
+        ),
+        rule(BOOLEAN_REGEX_STR, Some(TokenType::BoolLiteral)),
+        rule(BREAK_REGEX_STR, Some(TokenType::Keyword(Keyword::Break))),
+        rule(
+            BRACKET_CLOSE_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::BracketClose)),
+        ),
+        rule(
+            BRACKET_OPEN_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::BracketOpen)),
+        ),
+        rule(DO_REGEX_STR, Some(TokenType::Keyword(Keyword::Do))),
+        rule(ELSE_REGEX_STR, Some(TokenType::Keyword(Keyword::Else))),
+        rule(EQ_REGEX_STR, Some(TokenType::Operator(Operator::Eq))),
+        rule(FUN_REGEX_STR, Some(TokenType::Keyword(Keyword::Fun))),
+        rule(
+            GREATER_REGEX_STR,
+            Some(TokenType::Operator(Operator::Greater)),
+        ),
+        rule(GEQ_REGEX_STR, Some(TokenType::Operator(Operator::Geq))),
+        rule(IF_REGEX_STR, Some(TokenType::Keyword(Keyword::If))),
+        rule(INTEGER_REGEX_STR, Some(TokenType::IntLiteral)),
+        rule(LESS_REGEX_STR, Some(TokenType::Operator(Operator::Less))),
+        rule(LEQ_REGEX_STR, Some(TokenType::Operator(Operator::Leq))),
+        rule(MINUS_REGEX_STR, Some(TokenType::Operator(Operator::Minus))),
+        rule(NEQ_REGEX_STR, Some(TokenType::Operator(Operator::Neq))),
+        rule(NOT_REGEX_STR, Some(TokenType::Keyword(Keyword::Not))),
+        rule(OR_REGEX_STR, Some(TokenType::Keyword(Keyword::Or))),
+        rule(
+            PERCENT_REGEX_STR,
+            Some(TokenType::Operator(Operator::Percent)),
+        ),
+        rule(PLUS_REGEX_STR, Some(TokenType::Operator(Operator::Plus))),
+        rule(RETURN_REGEX_STR, Some(TokenType::Keyword(Keyword::Return))),
+        rule(
+            SEMICOLON_REGEX_STR,
+            Some(TokenType::Punctuation(Punctuation::Semicolon)),
+        ),
+        rule(SLASH_REGEX_STR, Some(TokenType::Operator(Operator::Slash))),
+        rule(THEN_REGEX_STR, Some(TokenType::Keyword(Keyword::Then))),
+        rule(UNIT_REGEX_STR, Some(TokenType::UnitLiteral)),
+        rule(VAR_REGEX_STR, Some(TokenType::Keyword(Keyword::Var))),
+        rule(WHILE_REGEX_STR, Some(TokenType::Keyword(Keyword::While))),
+        // Must be last so that keywords win ties
+        rule(VARIABLE_NAME_REGEX_STR, Some(TokenType::Identifier)),
+    ]
+}
+
+// Finds the rule with the longest match at the start of the input.
+// Returns the rule and the length of the match in bytes.
+fn longest_match<'a>(rules: &'a [TokenRule], input: &str) -> Option<(&'a TokenRule, usize)> {
+    let mut longest: Option<(&TokenRule, usize)> = None;
+    for rule in rules {
+        if let Some(found) = rule.regex.find(input) {
+            let is_longer = match longest {
+                Some((_, length)) => found.end() > length,
+                None => found.end() > 0,
+            };
+            if is_longer {
+                longest = Some((rule, found.end()));
+            }
+        }
+    }
+    longest
+}
+
+fn move_location(line: &mut usize, col: &mut usize, text: &str) {
+    for character in text.chars() {
+        if character == '\n' {
+            *line += 1;
+            *col = 1;
+        } else {
+            *col += 1;
+        }
+    }
+}
+
+// Name used in locations when the source does not come from a file
+pub const UNNAMED_SOURCE: &str = "<input>";
+
+pub fn tokenize(input: String) -> Result<Vec<Token>, LexError> {
+    tokenize_named(input, UNNAMED_SOURCE)
+}
+
+pub fn tokenize_file(path: &Path) -> Result<Vec<Token>, TokenizeFileError> {
+    let input = fs::read_to_string(path)?;
+    Ok(tokenize_named(input, &path.display().to_string())?)
+}
+
+pub fn tokenize_named(input: String, filename: &str) -> Result<Vec<Token>, LexError> {
+    let (tokens, errors) = tokenize_recovering(input, filename);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(tokens),
+    }
+}
+
+// Skips characters that do not start a token and keeps going, returning the
+// tokens found together with an error for every skipped character
+pub fn tokenize_recovering(input: String, filename: &str) -> (Vec<Token>, Vec<LexError>) {
+    let (mut tokens, errors) = tokenize_with_comments(input, filename);
+    tokens.retain(|token| *token.token_type() != TokenType::Comment);
+    (tokens, errors)
+}
+
+// Like `tokenize_recovering`, but keeps comments as tokens for tools that
+// reproduce the source, such as the formatter
+pub fn tokenize_with_comments(input: String, filename: &str) -> (Vec<Token>, Vec<LexError>) {
+    let rules = token_rules();
+
+    let mut position: usize = 0;
     let mut tokens: Vec<Token> = Vec::new();
+    let mut errors: Vec<LexError> = Vec::new();
 
     let mut col: usize = 1;
     let mut line: usize = 1;
 
-    while substring_end_index <= input.len() {
-        let slice_ends = char_is_empty(&input, substring_end_index);
-        let slice: &str = input
-            .get_mut(substring_start_index..substring_end_index)
-            .unwrap();
+    while position < input.len() {
+        let rest = &input[position..];
         let current_location: Location = Location {
-            filename: "TODO".to_string(),
-            line: line.clone(),
-            col: col.clone(),
+            filename: filename.to_string(),
+            line,
+            col,
+            offset: position,
         };
-        let mut new_line_matched: bool = false;
-        match slice_ends {
-            true => {
-                match slice {
-                    // Must match first
-                    // These regexes do not care if the slice ends or not
-                    s if new_line_regex.is_match(s) => new_line_matched = true,
-                    s if whitespace_regex.is_match(s) => {}
-                    s if comment_regex.is_match(s) => new_line_matched = true,
-                    s if eq_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if geq_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if leq_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if neq_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
- 
-                    // Rest alphabetically
-                    // Regexes demand that the next char after the slice is "empty"
-                    s if assign_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if asterix_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if comma_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if const_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if curly_close_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if curly_open_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if boolean_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if bracket_close_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if bracket_open_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if dash_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if else_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if greater_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if if_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if integer_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::IntLiteral,
-                        value: slice.to_string(),
-                    }),
-                    s if less_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if minus_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if plus_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Operator,
-                        value: slice.to_string(),
-                    }),
-                    s if semicolon_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Punctuation,
-                        value: slice.to_string(),
-                    }),
-                    s if unit_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::IntLiteral,
-                        value: slice.to_string(),
-                    }),
-                    s if var_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if while_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-                    s if variable_name_regex.is_match(s) => tokens.push(Token {
-                        location: current_location,
-                        token_type: TokenType::Identifier,
-                        value: slice.to_string(),
-                    }),
-
-                    // Nothing matched, move slice end index by one
-                    _ => {
-                        substring_end_index = substring_end_index + 1;
-                        continue;
-                    }
-                }
-            }
-            false => {
-                match slice {
-                    // These regexes do not care if the slice ends or not
-                    s if new_line_regex.is_match(s) => new_line_matched = true,
-                    s if whitespace_regex.is_match(s) => {}
-                    s if comment_regex.is_match(s) => new_line_matched = true,
-
-                    // Nothing matched, move slice end index by one
-                    _ => {
-                        substring_end_index = substring_end_index + 1;
-                        continue;
-                    }
-                }
+        let (rule, length) = match longest_match(&rules, rest) {
+            Some(found) => found,
+            None => {
+                let text = rest.chars().next().unwrap().to_string();
+                move_location(&mut line, &mut col, &text);
+                position += text.len();
+                errors.push(LexError {
+                    location: current_location,
+                    text,
+                });
+                continue;
             }
-        }
-
-        // Something matched, so move indexes.
-        move_indexes(&mut substring_start_index, &mut substring_end_index);
-
-        if new_line_matched {
-            handle_new_line(&mut line, &mut col);
-        } else {
-            // Move col
-            handle_col(&mut col, slice.len())
+        };
+        move_location(&mut line, &mut col, &rest[..length]);
+        position += length;
+        if let Some(token_type) = &rule.token_type {
+            tokens.push(Token {
+                location: current_location,
+                end_location: Location {
+                    filename: filename.to_string(),
+                    line,
+                    col,
+                    offset: position,
+                },
+                token_type: token_type.clone(),
+                value: rest[..length].to_string(),
+            });
         }
     }
-    return tokens;
+    (tokens, errors)
 }
+
 #[cfg(test)]
 mod tests {
     use crate::tokenizer::tokenize::*;
@@ -286,43 +367,71 @@ mod tests {
         let expected_tokens: Vec<Token> = vec![
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 1,
+                    offset: 0,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 3,
+                    offset: 2,
+                },
+                token_type: TokenType::Keyword(Keyword::If),
                 value: "if".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 5,
+                    offset: 4,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 6,
+                    offset: 5,
                 },
                 token_type: TokenType::IntLiteral,
                 value: "3".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 2,
                     col: 1,
+                    offset: 6,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 2,
+                    col: 6,
+                    offset: 11,
+                },
+                token_type: TokenType::Keyword(Keyword::While),
                 value: "while".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 2,
                     col: 7,
+                    offset: 12,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 2,
+                    col: 21,
+                    offset: 26,
                 },
                 token_type: TokenType::Identifier,
                 value: "_var_nameconst".to_string(),
             },
         ];
 
-        let tokens: Vec<Token> = tokenize("if  3\nwhile _var_nameconst ".to_string());
+        let tokens: Vec<Token> = tokenize("if  3\nwhile _var_nameconst ".to_string()).unwrap();
         assert_eq!(expected_tokens[0], tokens[0]);
         assert_eq!(expected_tokens[1], tokens[1]);
         assert_eq!(expected_tokens[2], tokens[2]);
@@ -334,79 +443,135 @@ mod tests {
         let expected_tokens: Vec<Token> = vec![
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 1,
+                    offset: 0,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 6,
+                    offset: 5,
                 },
                 token_type: TokenType::IntLiteral,
                 value: "12334".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 2,
                     col: 2,
+                    offset: 47,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 2,
+                    col: 7,
+                    offset: 52,
+                },
+                token_type: TokenType::Keyword(Keyword::Const),
                 value: "const".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 2,
                     col: 8,
+                    offset: 53,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 2,
+                    col: 21,
+                    offset: 66,
                 },
                 token_type: TokenType::Identifier,
                 value: "variable_name".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 2,
                     col: 22,
+                    offset: 67,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 2,
+                    col: 26,
+                    offset: 71,
+                },
+                token_type: TokenType::BoolLiteral,
                 value: "true".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 3,
                     col: 1,
+                    offset: 72,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 3,
+                    col: 6,
+                    offset: 77,
+                },
+                token_type: TokenType::Keyword(Keyword::While),
                 value: "while".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 6,
                     col: 1,
+                    offset: 84,
                 },
-                token_type: TokenType::IntLiteral,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 6,
+                    col: 5,
+                    offset: 88,
+                },
+                token_type: TokenType::UnitLiteral,
                 value: "unit".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 6,
                     col: 6,
+                    offset: 89,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 6,
+                    col: 8,
+                    offset: 91,
+                },
+                token_type: TokenType::Keyword(Keyword::If),
                 value: "if".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 6,
                     col: 9,
+                    offset: 92,
                 },
-                token_type: TokenType::Identifier,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 6,
+                    col: 13,
+                    offset: 96,
+                },
+                token_type: TokenType::Keyword(Keyword::Else),
                 value: "else".to_string(),
             },
         ];
 
-        let tokens: Vec<Token> = tokenize("12334 // This is a comment var const if else \n const variable_name true\nwhile    \n\n\nunit if else ".to_string());
+        let tokens: Vec<Token> = tokenize("12334 // This is a comment var const if else \n const variable_name true\nwhile    \n\n\nunit if else ".to_string()).unwrap();
         assert_eq!(expected_tokens[0], tokens[0]);
         assert_eq!(expected_tokens[1], tokens[1]);
         assert_eq!(expected_tokens[2], tokens[2]);
@@ -422,159 +587,295 @@ mod tests {
         let expected_tokens: Vec<Token> = vec![
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 1,
+                    offset: 0,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 2,
+                    offset: 1,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Plus),
                 value: "+".to_string(),
             }, 
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 3,
+                    offset: 2,
                 },
-                token_type: TokenType::Operator,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 4,
+                    offset: 3,
+                },
+                token_type: TokenType::Operator(Operator::Minus),
                 value: "-".to_string(),
             }, 
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 5,
+                    offset: 4,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 6,
+                    offset: 5,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Asterisk),
                 value: "*".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 7,
+                    offset: 6,
                 },
-                token_type: TokenType::Operator,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 8,
+                    offset: 7,
+                },
+                token_type: TokenType::Operator(Operator::Slash),
                 value: "/".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 9,
+                    offset: 8,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 10,
+                    offset: 9,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Assign),
                 value: "=".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 11,
+                    offset: 10,
                 },
-                token_type: TokenType::Punctuation,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 12,
+                    offset: 11,
+                },
+                token_type: TokenType::Punctuation(Punctuation::BracketOpen),
                 value: "(".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 13,
+                    offset: 12,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 15,
+                    offset: 14,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Eq),
                 value: "==".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 16,
+                    offset: 15,
                 },
-                token_type: TokenType::Punctuation,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 17,
+                    offset: 16,
+                },
+                token_type: TokenType::Punctuation(Punctuation::BracketClose),
                 value: ")".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 18,
+                    offset: 17,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 20,
+                    offset: 19,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Neq),
                 value: "!=".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 21,
+                    offset: 20,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 22,
+                    offset: 21,
                 },
-                token_type: TokenType::Punctuation,
+                token_type: TokenType::Punctuation(Punctuation::CurlyOpen),
                 value: "{".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 23,
+                    offset: 22,
                 },
-                token_type: TokenType::Operator,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 24,
+                    offset: 23,
+                },
+                token_type: TokenType::Operator(Operator::Less),
                 value: "<".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 25,
+                    offset: 24,
                 },
-                token_type: TokenType::Punctuation,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 26,
+                    offset: 25,
+                },
+                token_type: TokenType::Punctuation(Punctuation::CurlyClose),
                 value: "}".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 27,
+                    offset: 26,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 29,
+                    offset: 28,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Leq),
                 value: "<=".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 30,
+                    offset: 29,
                 },
-                token_type: TokenType::Punctuation,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 31,
+                    offset: 30,
+                },
+                token_type: TokenType::Punctuation(Punctuation::Comma),
                 value: ",".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 32,
+                    offset: 31,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 33,
+                    offset: 32,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Greater),
                 value: ">".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 34,
+                    offset: 33,
                 },
-                token_type: TokenType::Punctuation,
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 35,
+                    offset: 34,
+                },
+                token_type: TokenType::Punctuation(Punctuation::Semicolon),
                 value: ";".to_string(),
             },
             Token {
                 location: Location {
-                    filename: "TODO".to_string(),
+                    filename: "<input>".to_string(),
                     line: 1,
                     col: 36,
+                    offset: 35,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 38,
+                    offset: 37,
                 },
-                token_type: TokenType::Operator,
+                token_type: TokenType::Operator(Operator::Geq),
                 value: ">=".to_string(),
             },
+            Token {
+                location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 39,
+                    offset: 38,
+                },
+                end_location: Location {
+                    filename: "<input>".to_string(),
+                    line: 1,
+                    col: 40,
+                    offset: 39,
+                },
+                token_type: TokenType::Punctuation(Punctuation::Colon),
+                value: ":".to_string(),
+            },
         ];
-        let tokens: Vec<Token> = tokenize(r"+ - * / = ( == ) != { < } <= , > ; >=".to_string());
+        let tokens: Vec<Token> =
+            tokenize(r"+ - * / = ( == ) != { < } <= , > ; >= :".to_string()).unwrap();
         assert_eq!(expected_tokens[0], tokens[0]);
         assert_eq!(expected_tokens[1], tokens[1]);
         assert_eq!(expected_tokens[2], tokens[2]);
@@ -592,5 +893,262 @@ mod tests {
         assert_eq!(expected_tokens[14], tokens[14]);
         assert_eq!(expected_tokens[15], tokens[15]);
         assert_eq!(expected_tokens[16], tokens[16]);
+        assert_eq!(expected_tokens[17], tokens[17]);
+    }
+
+    fn token_values(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().map(|token| token.value()).collect()
+    }
+
+    #[test]
+    fn tokens_without_whitespace() {
+        assert_eq!(
+            token_values(&tokenize("f(x)".to_string()).unwrap()),
+            vec!["f", "(", "x", ")"]
+        );
+        assert_eq!(
+            token_values(&tokenize("a+b".to_string()).unwrap()),
+            vec!["a", "+", "b"]
+        );
+        assert_eq!(
+            token_values(&tokenize("x;".to_string()).unwrap()),
+            vec!["x", ";"]
+        );
+        assert_eq!(
+            token_values(&tokenize("{}".to_string()).unwrap()),
+            vec!["{", "}"]
+        );
+        assert_eq!(
+            token_values(&tokenize("a<=b==c!=d>=e=f".to_string()).unwrap()),
+            vec!["a", "<=", "b", "==", "c", "!=", "d", ">=", "e", "=", "f"]
+        );
+        assert_eq!(
+            token_values(&tokenize("var x:Int=12//comment\n{x}".to_string()).unwrap()),
+            vec!["var", "x", ":", "Int", "=", "12", "{", "x", "}"]
+        );
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let tokens = tokenize("iffy whiles variable truest unit_ if".to_string()).unwrap();
+        assert_eq!(
+            token_values(&tokens),
+            vec!["iffy", "whiles", "variable", "truest", "unit_", "if"]
+        );
+        for token in &tokens[..5] {
+            assert_eq!(token.token_type, TokenType::Identifier);
+        }
+        assert_eq!(tokens[5].token_type, TokenType::Keyword(Keyword::If));
+        let tokens = tokenize("123abc".to_string()).unwrap();
+        assert_eq!(token_values(&tokens), vec!["123", "abc"]);
+        assert_eq!(tokens[0].token_type, TokenType::IntLiteral);
+    }
+
+    #[test]
+    fn locations_without_whitespace() {
+        let tokens = tokenize("a<=b\n\t{x}".to_string()).unwrap();
+        let locations: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.location.line, token.location.col))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![(1, 1), (1, 2), (1, 4), (2, 2), (2, 3), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn unknown_character_is_an_error() {
+        let error = tokenize("a = 1;\nb = c @ 2".to_string()).unwrap_err();
+        assert_eq!(error.text(), "@");
+        assert_eq!(
+            error.location(),
+            &Location {
+                filename: "<input>".to_string(),
+                line: 2,
+                col: 7,
+                offset: 13,
+            }
+        );
+        assert_eq!(error.to_string(), "unexpected character '@' at <input>:2:7");
+        assert_eq!(tokenize("x$".to_string()).unwrap_err().text(), "$");
+        assert_eq!(tokenize("ä".to_string()).unwrap_err().text(), "ä");
+    }
+
+    #[test]
+    fn recovering_from_unknown_characters() {
+        let (tokens, errors) = tokenize_recovering("a $= 1;\n@@b".to_string(), "a.scl");
+        let values: Vec<&str> = tokens.iter().map(|token| token.value()).collect();
+        assert_eq!(values, ["a", "=", "1", ";", "b"]);
+        assert_eq!(tokens[4].location().col(), 3);
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "unexpected character '$' at a.scl:1:3",
+                "unexpected character '@' at a.scl:2:1",
+                "unexpected character '@' at a.scl:2:2",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeping_comments() {
+        let source = "a // one\n# two\nb";
+        let (tokens, errors) = tokenize_with_comments(source.to_string(), "a.scl");
+        assert!(errors.is_empty());
+        assert_eq!(token_values(&tokens), ["a", "// one", "# two", "b"]);
+        assert_eq!(tokens[1].token_type(), &TokenType::Comment);
+        assert_eq!(tokens[2].location().line(), 2);
+        assert_eq!(
+            token_values(&tokenize(source.to_string()).unwrap()),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn error_diagnostic() {
+        let source = "x = ä + 1";
+        let error = tokenize(source.to_string()).unwrap_err();
+        assert_eq!(
+            error.diagnostic().render(source, false),
+            "error: unexpected character 'ä'
+ --> <input>:1:5
+  |
+1 | x = ä + 1
+  |     ^
+"
+        );
+    }
+
+    #[test]
+    fn named_source() {
+        let tokens = tokenize_named("x\n  y".to_string(), "dir/file.scl").unwrap();
+        assert_eq!(tokens[1].location().filename(), "dir/file.scl");
+        assert_eq!(tokens[1].location().line(), 2);
+        assert_eq!(tokens[1].location().col(), 3);
+        let error = tokenize_named("@".to_string(), "file.scl").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected character '@' at file.scl:1:1"
+        );
+    }
+
+    #[test]
+    fn file_source() {
+        let path = std::env::temp_dir().join(format!("scl_tokenize_{}.scl", std::process::id()));
+        fs::write(&path, "var x = 1").unwrap();
+        let tokens = tokenize_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].location().filename(), path.display().to_string());
+        assert!(matches!(
+            tokenize_file(&path),
+            Err(TokenizeFileError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn spans() {
+        let source = "var äbc = 12;\n  foo(x)";
+        let error = tokenize(source.to_string()).unwrap_err();
+        assert_eq!(error.location().offset(), 4);
+        let source = "var abc = 12;\n  foo(x) // comment";
+        let tokens = tokenize(source.to_string()).unwrap();
+        for token in &tokens {
+            assert_eq!(&source[token.span()], token.value());
+        }
+        assert_eq!(tokens[1].span(), 4..7);
+        assert_eq!(tokens[5].span(), 16..19);
+        assert_eq!(tokens[5].end_location().line(), 2);
+        assert_eq!(tokens[5].end_location().col(), 6);
+    }
+
+    #[test]
+    fn literal_token_types() {
+        let tokens = tokenize("true false unit 12 x".to_string()).unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| token.token_type()).collect();
+        assert_eq!(
+            token_types,
+            vec![
+                &TokenType::BoolLiteral,
+                &TokenType::BoolLiteral,
+                &TokenType::UnitLiteral,
+                &TokenType::IntLiteral,
+                &TokenType::Identifier,
+            ]
+        );
+    }
+
+    #[test]
+    fn boolean_operator_keywords() {
+        let tokens = tokenize("not a and b or -c order".to_string()).unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| token.token_type()).collect();
+        assert_eq!(
+            token_types,
+            vec![
+                &TokenType::Keyword(Keyword::Not),
+                &TokenType::Identifier,
+                &TokenType::Keyword(Keyword::And),
+                &TokenType::Identifier,
+                &TokenType::Keyword(Keyword::Or),
+                &TokenType::Operator(Operator::Minus),
+                &TokenType::Identifier,
+                &TokenType::Identifier,
+            ]
+        );
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let tokens = tokenize("a%b/c".to_string()).unwrap();
+        assert_eq!(
+            tokens[1].token_type(),
+            &TokenType::Operator(Operator::Percent)
+        );
+        assert_eq!(
+            tokens[3].token_type(),
+            &TokenType::Operator(Operator::Slash)
+        );
+    }
+
+    #[test]
+    fn function_keywords() {
+        let tokens = tokenize("fun f(x: Int): Int { return x } funny returns".to_string()).unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| token.token_type()).collect();
+        assert_eq!(
+            token_types,
+            vec![
+                &TokenType::Keyword(Keyword::Fun),
+                &TokenType::Identifier,
+                &TokenType::Punctuation(Punctuation::BracketOpen),
+                &TokenType::Identifier,
+                &TokenType::Punctuation(Punctuation::Colon),
+                &TokenType::Identifier,
+                &TokenType::Punctuation(Punctuation::BracketClose),
+                &TokenType::Punctuation(Punctuation::Colon),
+                &TokenType::Identifier,
+                &TokenType::Punctuation(Punctuation::CurlyOpen),
+                &TokenType::Keyword(Keyword::Return),
+                &TokenType::Identifier,
+                &TokenType::Punctuation(Punctuation::CurlyClose),
+                &TokenType::Identifier,
+                &TokenType::Identifier,
+            ]
+        );
+    }
+
+    #[test]
+    fn loop_keywords() {
+        let tokens = tokenize("break continue breaks".to_string()).unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| token.token_type()).collect();
+        assert_eq!(
+            token_types,
+            vec![
+                &TokenType::Keyword(Keyword::Break),
+                &TokenType::Keyword(Keyword::Continue),
+                &TokenType::Identifier,
+            ]
+        );
     }
 }
@@ -0,0 +1,4 @@
+mod typecheck;
+mod types;
+pub use typecheck::{function_type, typecheck, typecheck_module, TypeChecker, TypeError};
+pub use types::Type;
@@ -0,0 +1,943 @@
+use std::fmt;
+
+use crate::builtins::builtin_types;
+use crate::diagnostics::{Diagnostic, Label, Span};
+use crate::parser::{
+    BinaryOperator, Expression, ExpressionKind, FunctionDefinition, Module, TypeAnnotation,
+    UnaryOperator,
+};
+use crate::symbol_table::SymbolTable;
+use crate::tokenizer::Location;
+
+use super::types::Type;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeError {
+    span: Span,
+    message: String,
+    // Related places in the source, like an earlier definition
+    labels: Vec<Label>,
+}
+
+impl TypeError {
+    fn new(span: &Span, message: String) -> TypeError {
+        TypeError {
+            span: span.clone(),
+            message,
+            labels: Vec::new(),
+        }
+    }
+
+    fn with_label(mut self, span: &Span, message: &str) -> TypeError {
+        self.labels
+            .push(Label::new(span.clone(), message.to_string()));
+        self
+    }
+
+    pub fn location(&self) -> &Location {
+        self.span.location()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::at(self.message.clone(), self.span.clone()).with_labels(&self.labels)
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.location())
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+#[derive(Clone, Debug)]
+struct Variable {
+    ty: Type,
+    constant: bool,
+}
+
+fn expect_type(expected: &Type, actual: &Type, span: &Span) -> Result<(), TypeError> {
+    if !expected.accepts(actual) {
+        return Err(TypeError::new(
+            span,
+            format!("expected type {}, got {}", expected, actual),
+        ));
+    }
+    Ok(())
+}
+
+fn resolve_annotation(annotation: &TypeAnnotation) -> Result<Type, TypeError> {
+    match Type::from_name(&annotation.name) {
+        Some(ty) => Ok(ty),
+        None => Err(TypeError::new(
+            &annotation.span(),
+            format!("unknown type '{}'", annotation.name),
+        )),
+    }
+}
+
+// The type of a function from its annotations
+pub fn function_type(function: &FunctionDefinition) -> Result<Type, TypeError> {
+    let mut parameters = Vec::new();
+    for parameter in &function.parameters {
+        parameters.push(resolve_annotation(&parameter.type_annotation)?);
+    }
+    let return_type = match &function.return_type {
+        Some(annotation) => resolve_annotation(annotation)?,
+        None => Type::Unit,
+    };
+    Ok(Type::function(parameters, return_type))
+}
+
+// The name of the function generated from the top-level expressions
+const MAIN_FUNCTION: &str = "main";
+
+// Symbols of the runtime that are not builtins and so could otherwise be
+// defined again by a program
+const RUNTIME_SYMBOLS: [&str; 2] = ["_start", "_division_by_zero"];
+
+// Assigns a type to every expression node. The outermost scope holds the
+// globals, such as builtin functions, and survives between calls to `check`.
+#[derive(Clone)]
+pub struct TypeChecker {
+    symbols: SymbolTable<Variable>,
+    // Return type of the function being checked, None at the top level
+    return_type: Option<Type>,
+    // Type of the values each enclosing loop breaks with, innermost last.
+    // None until a `break` is seen.
+    break_types: Vec<Option<Type>>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        TypeChecker::new()
+    }
+}
+
+impl TypeChecker {
+    // Starts with the builtins declared
+    pub fn new() -> TypeChecker {
+        let mut checker = TypeChecker {
+            symbols: SymbolTable::new(),
+            return_type: None,
+            break_types: Vec::new(),
+        };
+        for (name, ty) in builtin_types() {
+            checker.declare_global(name, ty);
+        }
+        checker
+    }
+
+    pub fn declare_global(&mut self, name: &str, ty: Type) {
+        let variable = Variable { ty, constant: true };
+        match self.symbols.lookup_mut(name) {
+            Some(existing) => *existing = variable,
+            None => {
+                self.symbols.declare(name, variable);
+            }
+        }
+    }
+
+    // Functions are declared before any body is checked, so they can call
+    // each other regardless of order. The top-level expressions get a scope
+    // of their own that function bodies cannot see.
+    pub fn check_module(&mut self, module: &mut Module) -> Result<Type, TypeError> {
+        self.declare_functions(&module.functions, false)?;
+        for function in &mut module.functions {
+            self.check_function(function)?;
+        }
+        self.symbols.push_scope();
+        let ty = self.check(&mut module.body);
+        self.symbols.pop_scope();
+        ty
+    }
+
+    // Checks one input of the REPL, parsed with `parse_input`. Unlike in
+    // `check_module`, the top-level expressions are checked in the outermost
+    // scope so that their variables stay around for the following inputs,
+    // and functions from earlier inputs can be redefined with the same type.
+    pub fn check_input(&mut self, module: &mut Module) -> Result<Type, TypeError> {
+        self.declare_functions(&module.functions, true)?;
+        for function in &mut module.functions {
+            self.check_function(function)?;
+        }
+        let ty = match &mut module.body.kind {
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => self.check_block(expressions, result)?,
+            _ => self.check(&mut module.body)?,
+        };
+        module.body.ty = Some(ty.clone());
+        Ok(ty)
+    }
+
+    fn declare_functions(
+        &mut self,
+        functions: &[FunctionDefinition],
+        allow_redefinition: bool,
+    ) -> Result<(), TypeError> {
+        for (index, function) in functions.iter().enumerate() {
+            let ty = function_type(function)?;
+            if function.name == MAIN_FUNCTION {
+                return Err(TypeError::new(
+                    &function.span(),
+                    format!("'{}' is reserved for the top-level code", MAIN_FUNCTION),
+                ));
+            }
+            if RUNTIME_SYMBOLS.contains(&function.name.as_str()) {
+                return Err(TypeError::new(
+                    &function.span(),
+                    format!("'{}' is reserved for the runtime", function.name),
+                ));
+            }
+            let error = TypeError::new(
+                &function.span(),
+                format!("'{}' is already defined", function.name),
+            );
+            let previous = functions[..index]
+                .iter()
+                .find(|previous| previous.name == function.name);
+            if let Some(previous) = previous {
+                return Err(error.with_label(&previous.span(), "first defined here"));
+            }
+            let redefinition = match self.symbols.lookup(&function.name) {
+                Some(existing) => allow_redefinition && existing.constant && existing.ty == ty,
+                None => false,
+            };
+            let variable = Variable { ty, constant: true };
+            if !redefinition && !self.symbols.declare(&function.name, variable) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_function(&mut self, function: &mut FunctionDefinition) -> Result<(), TypeError> {
+        let return_type = match &function.return_type {
+            Some(annotation) => resolve_annotation(annotation)?,
+            None => Type::Unit,
+        };
+        let outer_scopes = self.symbols.take_inner_scopes();
+        self.symbols.push_scope();
+        let outer_return_type = self.return_type.replace(return_type.clone());
+        let body_type = self.check_function_body(function);
+        self.return_type = outer_return_type;
+        self.symbols.restore_inner_scopes(outer_scopes);
+        let body_type = body_type?;
+        if !return_type.accepts(&body_type) {
+            let error = TypeError::new(
+                &function.body.span(),
+                format!(
+                    "function '{}' should return {}, but its body has type {}",
+                    function.name, return_type, body_type
+                ),
+            );
+            return Err(match &function.return_type {
+                Some(annotation) => {
+                    error.with_label(&annotation.span(), "return type declared here")
+                }
+                None => error,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_function_body(
+        &mut self,
+        function: &mut FunctionDefinition,
+    ) -> Result<Type, TypeError> {
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            let variable = Variable {
+                ty: resolve_annotation(&parameter.type_annotation)?,
+                constant: false,
+            };
+            if !self.symbols.declare(&parameter.name, variable) {
+                let error = TypeError::new(
+                    &parameter.span(),
+                    format!("parameter '{}' is declared twice", parameter.name),
+                );
+                let first = function.parameters[..index]
+                    .iter()
+                    .find(|first| first.name == parameter.name);
+                return Err(match first {
+                    Some(first) => error.with_label(&first.span(), "first declared here"),
+                    None => error,
+                });
+            }
+        }
+        self.check(&mut function.body)
+    }
+
+    pub fn check(&mut self, expression: &mut Expression) -> Result<Type, TypeError> {
+        let span = expression.span();
+        let ty = self.check_kind(&mut expression.kind, &span)?;
+        expression.ty = Some(ty.clone());
+        Ok(ty)
+    }
+
+    fn check_kind(&mut self, kind: &mut ExpressionKind, span: &Span) -> Result<Type, TypeError> {
+        match kind {
+            ExpressionKind::IntLiteral(_) => Ok(Type::Int),
+            ExpressionKind::BoolLiteral(_) => Ok(Type::Bool),
+            ExpressionKind::UnitLiteral => Ok(Type::Unit),
+            ExpressionKind::Identifier(name) => match self.symbols.lookup(name) {
+                Some(variable) => Ok(variable.ty.clone()),
+                None => Err(TypeError::new(
+                    span,
+                    format!("undefined variable '{}'", name),
+                )),
+            },
+            ExpressionKind::UnaryOp { operator, operand } => {
+                let operand_type = self.check(operand)?;
+                let expected = match operator {
+                    UnaryOperator::Negate => Type::Int,
+                    UnaryOperator::Not => Type::Bool,
+                };
+                expect_type(&expected, &operand_type, &operand.span())?;
+                Ok(expected)
+            }
+            ExpressionKind::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let left_type = self.check(left)?;
+                let right_type = self.check(right)?;
+                self.check_binary_op(*operator, &left_type, &right_type, span)
+            }
+            ExpressionKind::Assignment { name, value } => {
+                let value_type = self.check(value)?;
+                let variable = match self.symbols.lookup(name) {
+                    Some(variable) => variable,
+                    None => {
+                        return Err(TypeError::new(
+                            span,
+                            format!("undefined variable '{}'", name),
+                        ))
+                    }
+                };
+                if variable.constant {
+                    return Err(TypeError::new(
+                        span,
+                        format!("cannot assign to constant '{}'", name),
+                    ));
+                }
+                expect_type(&variable.ty, &value_type, &value.span())?;
+                Ok(value_type)
+            }
+            ExpressionKind::Call { name, arguments } => {
+                let function_type = match self.symbols.lookup(name) {
+                    Some(variable) => variable.ty.clone(),
+                    None => {
+                        return Err(TypeError::new(
+                            span,
+                            format!("undefined function '{}'", name),
+                        ))
+                    }
+                };
+                let (parameters, return_type) = match function_type {
+                    Type::Function {
+                        parameters,
+                        return_type,
+                    } => (parameters, return_type),
+                    other => {
+                        return Err(TypeError::new(
+                            span,
+                            format!("'{}' is not a function, it has type {}", name, other),
+                        ))
+                    }
+                };
+                if parameters.len() != arguments.len() {
+                    return Err(TypeError::new(
+                        span,
+                        format!(
+                            "function '{}' takes {} arguments but {} were given",
+                            name,
+                            parameters.len(),
+                            arguments.len()
+                        ),
+                    ));
+                }
+                for (parameter, argument) in parameters.iter().zip(arguments.iter_mut()) {
+                    let argument_type = self.check(argument)?;
+                    expect_type(parameter, &argument_type, &argument.span())?;
+                }
+                Ok(*return_type)
+            }
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => {
+                self.symbols.push_scope();
+                let ty = self.check_block(expressions, result);
+                self.symbols.pop_scope();
+                ty
+            }
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition_type = self.check(condition)?;
+                expect_type(&Type::Bool, &condition_type, &condition.span())?;
+                let then_type = self.check(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        let else_type = self.check(else_branch)?;
+                        if then_type == Type::Never {
+                            return Ok(else_type);
+                        }
+                        expect_type(&then_type, &else_type, &else_branch.span())?;
+                        Ok(then_type)
+                    }
+                    None => Ok(Type::Unit),
+                }
+            }
+            ExpressionKind::While { condition, body } => {
+                let condition_type = self.check(condition)?;
+                expect_type(&Type::Bool, &condition_type, &condition.span())?;
+                self.break_types.push(None);
+                let body_type = self.check(body);
+                let break_type = self.break_types.pop().unwrap();
+                body_type?;
+                match break_type {
+                    None | Some(Type::Unit) | Some(Type::Never) => Ok(Type::Unit),
+                    // The loop must not end without a value
+                    Some(ty) => match condition.kind {
+                        ExpressionKind::BoolLiteral(true) => Ok(ty),
+                        _ => Err(TypeError::new(
+                            span,
+                            "a loop that breaks with a value must have 'true' as its condition"
+                                .to_string(),
+                        )),
+                    },
+                }
+            }
+            ExpressionKind::VarDeclaration {
+                name,
+                constant,
+                type_annotation,
+                initializer,
+            } => {
+                let initializer_type = self.check(initializer)?;
+                // An initializer that never finishes gives no type to the
+                // variable, so it must come from the annotation
+                let ty = match type_annotation {
+                    Some(annotation) => {
+                        let annotated_type = resolve_annotation(annotation)?;
+                        expect_type(&annotated_type, &initializer_type, &initializer.span())?;
+                        annotated_type
+                    }
+                    None if initializer_type == Type::Never => {
+                        return Err(TypeError::new(
+                            &initializer.span(),
+                            format!(
+                            "cannot infer the type of '{}' from an expression that never finishes",
+                            name
+                        ),
+                        ))
+                    }
+                    None => initializer_type,
+                };
+                let variable = Variable {
+                    ty,
+                    constant: *constant,
+                };
+                if !self.symbols.declare(name, variable) {
+                    return Err(TypeError::new(
+                        span,
+                        format!("variable '{}' is already declared in this scope", name),
+                    ));
+                }
+                Ok(Type::Unit)
+            }
+            ExpressionKind::Return { value } => {
+                let return_type = match &self.return_type {
+                    Some(return_type) => return_type.clone(),
+                    None => {
+                        return Err(TypeError::new(
+                            span,
+                            "'return' is only allowed inside a function".to_string(),
+                        ))
+                    }
+                };
+                match value {
+                    Some(value) => {
+                        let value_type = self.check(value)?;
+                        expect_type(&return_type, &value_type, &value.span())?;
+                    }
+                    None => expect_type(&return_type, &Type::Unit, span)?,
+                }
+                Ok(Type::Never)
+            }
+            ExpressionKind::Break { value } => {
+                let value_type = match value {
+                    Some(value) => self.check(value)?,
+                    None => Type::Unit,
+                };
+                let break_type = match self.break_types.last_mut() {
+                    Some(break_type) => break_type,
+                    None => {
+                        return Err(TypeError::new(
+                            span,
+                            "'break' is only allowed inside a loop".to_string(),
+                        ))
+                    }
+                };
+                match break_type {
+                    Some(expected) => {
+                        let value_span = match value {
+                            Some(value) => value.span(),
+                            None => span.clone(),
+                        };
+                        expect_type(expected, &value_type, &value_span)?;
+                    }
+                    None => *break_type = Some(value_type),
+                }
+                Ok(Type::Never)
+            }
+            ExpressionKind::Continue => {
+                if self.break_types.is_empty() {
+                    return Err(TypeError::new(
+                        span,
+                        "'continue' is only allowed inside a loop".to_string(),
+                    ));
+                }
+                Ok(Type::Never)
+            }
+        }
+    }
+
+    fn check_block(
+        &mut self,
+        expressions: &mut [Expression],
+        result: &mut Option<Box<Expression>>,
+    ) -> Result<Type, TypeError> {
+        let mut last_type = Type::Unit;
+        for expression in expressions {
+            last_type = self.check(expression)?;
+        }
+        // `{ ...; return x; }` never finishes either
+        match result {
+            Some(result) => self.check(result),
+            None if last_type == Type::Never => Ok(Type::Never),
+            None => Ok(Type::Unit),
+        }
+    }
+
+    fn check_binary_op(
+        &self,
+        operator: BinaryOperator,
+        left_type: &Type,
+        right_type: &Type,
+        span: &Span,
+    ) -> Result<Type, TypeError> {
+        let (operand_type, result_type) = match operator {
+            BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                if !left_type.accepts(right_type) && !right_type.accepts(left_type) {
+                    return Err(TypeError::new(
+                        span,
+                        format!(
+                            "cannot compare {} with {} using '{}'",
+                            left_type, right_type, operator
+                        ),
+                    ));
+                }
+                return Ok(Type::Bool);
+            }
+            BinaryOperator::Add
+            | BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Remainder => (Type::Int, Type::Int),
+            BinaryOperator::Less
+            | BinaryOperator::LessOrEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterOrEqual => (Type::Int, Type::Bool),
+            BinaryOperator::And | BinaryOperator::Or => (Type::Bool, Type::Bool),
+        };
+        if !operand_type.accepts(left_type) || !operand_type.accepts(right_type) {
+            return Err(TypeError::new(
+                span,
+                format!(
+                    "operator '{}' expects operands of type {}, got {} and {}",
+                    operator, operand_type, left_type, right_type
+                ),
+            ));
+        }
+        Ok(result_type)
+    }
+}
+
+pub fn typecheck(expression: &mut Expression) -> Result<Type, TypeError> {
+    TypeChecker::new().check(expression)
+}
+
+pub fn typecheck_module(module: &mut Module) -> Result<Type, TypeError> {
+    TypeChecker::new().check_module(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{parse, parse_module};
+    use crate::tokenizer::{tokenize, UNNAMED_SOURCE};
+    use crate::typechecker::typecheck::*;
+
+    fn parse_source(source: &str) -> Expression {
+        parse(tokenize(source.to_string()).unwrap()).unwrap()
+    }
+
+    fn check_source(source: &str) -> Result<Type, TypeError> {
+        typecheck(&mut parse_source(source))
+    }
+
+    fn error_message(source: &str) -> String {
+        check_source(source).unwrap_err().message().to_string()
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(check_source("1"), Ok(Type::Int));
+        assert_eq!(check_source("true"), Ok(Type::Bool));
+        assert_eq!(check_source("unit"), Ok(Type::Unit));
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(check_source("1 + 2 * 3 % 4"), Ok(Type::Int));
+        assert_eq!(check_source("1 < 2"), Ok(Type::Bool));
+        assert_eq!(check_source("1 == 2 and true != false"), Ok(Type::Bool));
+        assert_eq!(check_source("not true or false"), Ok(Type::Bool));
+        assert_eq!(check_source("-1"), Ok(Type::Int));
+        assert_eq!(check_source("unit == unit"), Ok(Type::Bool));
+    }
+
+    #[test]
+    fn operator_type_errors() {
+        assert_eq!(
+            error_message("true + 1"),
+            "operator '+' expects operands of type Int, got Bool and Int"
+        );
+        assert!(check_source("1 and true").is_err());
+        assert!(check_source("true < false").is_err());
+        assert!(check_source("-true").is_err());
+        assert!(check_source("not 1").is_err());
+        assert_eq!(
+            error_message("1 == true"),
+            "cannot compare Int with Bool using '=='"
+        );
+    }
+
+    #[test]
+    fn if_and_while() {
+        assert_eq!(check_source("if true then 1 else 2"), Ok(Type::Int));
+        assert_eq!(check_source("if true then 1"), Ok(Type::Unit));
+        assert_eq!(check_source("while false do 1"), Ok(Type::Unit));
+        assert_eq!(
+            error_message("if 3 then 1 else 2"),
+            "expected type Bool, got Int"
+        );
+        assert!(check_source("if true then 1 else false").is_err());
+        assert!(check_source("while 1 do 1").is_err());
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(check_source("var x = 1; x"), Ok(Type::Int));
+        assert_eq!(check_source("var x: Int = 1; x = 2"), Ok(Type::Int));
+        assert_eq!(
+            check_source("var x: Bool = true; { var x = 1; x }"),
+            Ok(Type::Int)
+        );
+        assert_eq!(check_source("var x = 1;"), Ok(Type::Unit));
+        assert_eq!(
+            error_message("var x: Bool = 1"),
+            "expected type Bool, got Int"
+        );
+        assert_eq!(error_message("var x: Foo = 1"), "unknown type 'Foo'");
+        assert_eq!(
+            error_message("var x = 1; x = true"),
+            "expected type Int, got Bool"
+        );
+        assert_eq!(error_message("y"), "undefined variable 'y'");
+        assert_eq!(error_message("{ var y = 1 }; y"), "undefined variable 'y'");
+        assert_eq!(
+            error_message("var x = 1; var x = 2"),
+            "variable 'x' is already declared in this scope"
+        );
+        assert_eq!(
+            error_message("const x = 1; x = 2"),
+            "cannot assign to constant 'x'"
+        );
+    }
+
+    #[test]
+    fn function_calls() {
+        let mut checker = TypeChecker::new();
+        checker.declare_global("f", Type::function(vec![Type::Int, Type::Bool], Type::Int));
+        assert_eq!(
+            checker.check(&mut parse_source("f(1, true) + 1")),
+            Ok(Type::Int)
+        );
+        let error = checker.check(&mut parse_source("f(1)")).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "function 'f' takes 2 arguments but 1 were given"
+        );
+        let error = checker.check(&mut parse_source("f(1, 2)")).unwrap_err();
+        assert_eq!(error.message(), "expected type Bool, got Int");
+        assert_eq!(error_message("g()"), "undefined function 'g'");
+        assert_eq!(
+            error_message("var g = 1; g()"),
+            "'g' is not a function, it has type Int"
+        );
+    }
+
+    #[test]
+    fn every_node_gets_a_type() {
+        let mut expression = parse_source("{ var x = 1; if x < 2 then true else false }");
+        typecheck(&mut expression).unwrap();
+        assert_eq!(expression.ty, Some(Type::Bool));
+        match &expression.kind {
+            ExpressionKind::Block {
+                expressions,
+                result,
+            } => {
+                assert_eq!(expressions[0].ty, Some(Type::Unit));
+                let result = result.as_ref().unwrap();
+                match &result.kind {
+                    ExpressionKind::If { condition, .. } => {
+                        assert_eq!(condition.ty, Some(Type::Bool));
+                    }
+                    _ => panic!("expected an if expression"),
+                }
+            }
+            _ => panic!("expected a block"),
+        }
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let tokens = tokenize("var x = 1;\nx = true".to_string()).unwrap();
+        let error = typecheck(&mut parse(tokens.clone()).unwrap()).unwrap_err();
+        assert_eq!(error.location(), tokens[7].location());
+    }
+
+    #[test]
+    fn builtins_are_declared() {
+        assert_eq!(check_source("print_int(read_int())"), Ok(Type::Unit));
+        assert_eq!(check_source("print_bool(1 < 2)"), Ok(Type::Unit));
+        assert_eq!(
+            error_message("print_bool(1)"),
+            "expected type Bool, got Int"
+        );
+        assert_eq!(
+            error_message("print_int = read_int"),
+            "cannot assign to constant 'print_int'"
+        );
+    }
+
+    fn check_module_source(source: &str) -> Result<Type, TypeError> {
+        let mut module =
+            parse_module(tokenize(source.to_string()).unwrap(), UNNAMED_SOURCE).unwrap();
+        typecheck_module(&mut module)
+    }
+
+    fn module_error(source: &str) -> String {
+        check_module_source(source)
+            .unwrap_err()
+            .message()
+            .to_string()
+    }
+
+    #[test]
+    fn error_labels() {
+        let render = |source: &str| {
+            check_module_source(source)
+                .unwrap_err()
+                .diagnostic()
+                .render(source, false)
+        };
+        assert_eq!(
+            render("fun f() {}\nfun f() {}"),
+            "error: 'f' is already defined
+ --> <input>:2:1
+  |
+1 | fun f() {}
+  | ---------- first defined here
+2 | fun f() {}
+  | ^^^^^^^^^^
+"
+        );
+        assert_eq!(
+            render("fun f(): Int {\n  true\n}"),
+            "error: function 'f' should return Int, but its body has type Bool
+ --> <input>:1:14
+  |
+1 | fun f(): Int {
+  |              ^
+  |          --- return type declared here
+"
+        );
+        assert_eq!(
+            check_module_source("fun f(a: Int, a: Int) {}")
+                .unwrap_err()
+                .labels()[0]
+                .span()
+                .location()
+                .col(),
+            7
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            check_module_source(
+                "fun is_even(n: Int): Bool { if n == 0 then true else is_odd(n - 1) }
+                fun is_odd(n: Int): Bool { if n == 0 then false else is_even(n - 1) }
+                fun show(n: Int) { print_bool(is_even(n)) }
+                show(3); is_odd(3)"
+            ),
+            Ok(Type::Bool)
+        );
+        assert_eq!(
+            check_module_source("fun f(a: Int, b: Bool): Int { a } f"),
+            Ok(Type::function(vec![Type::Int, Type::Bool], Type::Int))
+        );
+        assert_eq!(
+            module_error("fun f(a: Int): Int { a } f(true)"),
+            "expected type Int, got Bool"
+        );
+        assert_eq!(
+            module_error("fun f(): Int { true }"),
+            "function 'f' should return Int, but its body has type Bool"
+        );
+        assert_eq!(
+            module_error("fun f(a: Int): Int { a } fun f(): Int { 1 }"),
+            "'f' is already defined"
+        );
+        assert_eq!(
+            module_error("fun print_int(a: Int) {}"),
+            "'print_int' is already defined"
+        );
+        assert_eq!(
+            module_error("fun main() {}"),
+            "'main' is reserved for the top-level code"
+        );
+        assert_eq!(
+            module_error("fun _start() {} 1"),
+            "'_start' is reserved for the runtime"
+        );
+        assert_eq!(
+            module_error("fun read_int(): Int { 1 }"),
+            "'read_int' is already defined"
+        );
+        assert_eq!(
+            module_error("fun f(a: Int, a: Bool) {}"),
+            "parameter 'a' is declared twice"
+        );
+        assert_eq!(module_error("fun f(a: Float) {}"), "unknown type 'Float'");
+        assert_eq!(
+            module_error("fun f(a: Int) { a = 2; f = f }"),
+            "cannot assign to constant 'f'"
+        );
+    }
+
+    #[test]
+    fn functions_only_see_globals() {
+        assert_eq!(
+            module_error("var x = 1; fun f(): Int { x }"),
+            "undefined variable 'x'"
+        );
+        assert_eq!(module_error("fun f(a: Int) {} a"), "undefined variable 'a'");
+    }
+
+    #[test]
+    fn return_expressions() {
+        assert_eq!(
+            check_module_source(
+                "fun abs(n: Int): Int { if n < 0 then return -n; n }
+                fun sign(n: Int): Int { if n < 0 then return -1 else if n > 0 then 1 else 0 }
+                fun first(): Int { while true do { return 1; } return 2; }
+                fun nothing() { return; }
+                abs(-1) + sign(2) + first()"
+            ),
+            Ok(Type::Int)
+        );
+        assert_eq!(
+            module_error("fun f(): Int { return true }"),
+            "expected type Int, got Bool"
+        );
+        assert_eq!(
+            module_error("fun f(): Int { return; }"),
+            "expected type Int, got Unit"
+        );
+        assert_eq!(
+            error_message("return 1"),
+            "'return' is only allowed inside a function"
+        );
+        assert_eq!(
+            module_error("fun f(): Int { var x = return 1; x + 1 }"),
+            "cannot infer the type of 'x' from an expression that never finishes"
+        );
+        assert_eq!(
+            module_error("fun f(): Int { var x: Int = return 1; x == true }"),
+            "cannot compare Int with Bool using '=='"
+        );
+        assert_eq!(
+            check_module_source("fun f(): Int { var x: Int = return 1; x + 1 } f()"),
+            Ok(Type::Int)
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(
+            check_source("while true do { if 1 < 2 then continue; break }"),
+            Ok(Type::Unit)
+        );
+        assert_eq!(
+            check_source("var x = while true do { if 1 < 2 then break 1; break 2 }; x"),
+            Ok(Type::Int)
+        );
+        assert_eq!(
+            check_source("while 1 < 2 do while true do break true"),
+            Ok(Type::Unit)
+        );
+        assert_eq!(
+            error_message("while true do { if true then break 1; break false }"),
+            "expected type Int, got Bool"
+        );
+        assert_eq!(
+            error_message("while true do { break 1; break }"),
+            "expected type Int, got Unit"
+        );
+        assert_eq!(
+            error_message("while 1 < 2 do break 1"),
+            "a loop that breaks with a value must have 'true' as its condition"
+        );
+        assert_eq!(
+            error_message("break"),
+            "'break' is only allowed inside a loop"
+        );
+        assert_eq!(
+            error_message("if true then continue"),
+            "'continue' is only allowed inside a loop"
+        );
+        assert_eq!(
+            error_message("while (break) do 1"),
+            "'break' is only allowed inside a loop"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    Unit,
+    // Type of expressions that never produce a value, such as `return`
+    Never,
+    Function {
+        parameters: Vec<Type>,
+        return_type: Box<Type>,
+    },
+}
+
+impl Type {
+    // Resolves the name used in type annotations
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Int" => Some(Type::Int),
+            "Bool" => Some(Type::Bool),
+            "Unit" => Some(Type::Unit),
+            _ => None,
+        }
+    }
+
+    // Never fits wherever a value is expected, since control does not
+    // continue past it
+    pub fn accepts(&self, actual: &Type) -> bool {
+        self == actual || *actual == Type::Never
+    }
+
+    pub fn function(parameters: Vec<Type>, return_type: Type) -> Type {
+        Type::Function {
+            parameters,
+            return_type: Box::new(return_type),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Unit => write!(f, "Unit"),
+            Type::Never => write!(f, "Never"),
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                write!(f, "(")?;
+                for (index, parameter) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", parameter)?;
+                }
+                write!(f, ") => {}", return_type)
+            }
+        }
+    }
+}